@@ -12,13 +12,15 @@ use crate::{
 
 #[derive(Error, Clone, PartialEq, PartialOrd, Debug)]
 pub enum CompilerError<'s> {
-    #[error("Expected {expected} on line {}, but got {}", .token.line, .token.typ)]
+    #[error("Expected {expected} on line {}, column {}, but got {}", .token.line, .token.column, .token.typ)]
     UnexpectedToken {
         expected: TokenType<'s>,
         token: &'s Token<'s>,
     },
     #[error("Unexpected end of input")]
     UnexpectedEndOfInput,
+    #[error("The {} operator on line {}, column {} is only supported by the tree-walker", .token.typ, .token.line, .token.column)]
+    UnsupportedOperator { token: &'s Token<'s> },
 }
 
 type IntermediateCompileResult<'s> = Result<(), CompilerError<'s>>;
@@ -62,6 +64,43 @@ where
         Ok(())
     }
 
+    /// Take the next token, which must be of type `expected`.
+    fn consume(&mut self, expected: TokenType<'s>) -> Result<&'s Token<'s>, CompilerError<'s>> {
+        match self.tokens.next() {
+            Some(token) if token.typ == expected => Ok(token),
+            Some(token) => Err(CompilerError::UnexpectedToken { expected, token }),
+            None => Err(CompilerError::UnexpectedEndOfInput),
+        }
+    }
+
+    fn statement(&mut self) -> IntermediateCompileResult<'s> {
+        match self.tokens.peek() {
+            Some(Token {
+                typ: TokenType::Print,
+                ..
+            }) => self.print_statement(),
+            _ => self.expression_statement(),
+        }
+    }
+
+    fn print_statement(&mut self) -> IntermediateCompileResult<'s> {
+        let keyword = self.consume(TokenType::Print)?;
+        self.expression()?;
+        self.consume(TokenType::Semicolon)?;
+        self.chunk.write(OpCode::Print, keyword.line);
+
+        Ok(())
+    }
+
+    fn expression_statement(&mut self) -> IntermediateCompileResult<'s> {
+        self.expression()?;
+        let semicolon = self.consume(TokenType::Semicolon)?;
+        // The value is only computed for its side effects, so don't leave it on the stack.
+        self.chunk.write(OpCode::Pop, semicolon.line);
+
+        Ok(())
+    }
+
     fn expression(&mut self) -> IntermediateCompileResult<'s> {
         self.parse(Precedence::Assignment)?;
 
@@ -73,25 +112,22 @@ where
             match token.typ {
                 TokenType::LeftParen => {
                     self.expression()?;
-
-                    if let Some(token) = self.tokens.next() {
-                        if token.typ != TokenType::RightParen {
-                            return Err(CompilerError::UnexpectedToken {
-                                expected: TokenType::RightParen,
-                                token,
-                            });
-                        }
-                    } else {
-                        return Err(CompilerError::UnexpectedEndOfInput);
-                    }
+                    self.consume(TokenType::RightParen)?;
                 }
                 TokenType::Minus => {
                     self.parse(Precedence::Unary)?;
                     self.chunk.write(OpCode::Negate, token.line);
                 }
-                TokenType::Number(_) => {
+                TokenType::Bang => {
+                    self.parse(Precedence::Unary)?;
+                    self.chunk.write(OpCode::Not, token.line);
+                }
+                TokenType::Number(_) | TokenType::String(_) => {
                     self.chunk.add_constant(Value::from(&token.typ), token.line);
                 }
+                TokenType::True => self.chunk.write(OpCode::True, token.line),
+                TokenType::False => self.chunk.write(OpCode::False, token.line),
+                TokenType::Nil => self.chunk.write(OpCode::Nil, token.line),
                 _ => {
                     return Err(CompilerError::UnexpectedToken {
                         expected: TokenType::Number(0.0),
@@ -115,11 +151,34 @@ where
                             TokenType::Minus => OpCode::Subtract,
                             TokenType::Star => OpCode::Multiply,
                             TokenType::Slash => OpCode::Divide,
-                            _ => unreachable!(""),
+                            _ => unreachable!("Only arithmetic operators reach this arm"),
                         },
                         token.line,
                     );
                 }
+                TokenType::EqualEqual
+                | TokenType::BangEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual => {
+                    self.parse(token.typ.precedence().next())?;
+                    self.chunk.write(
+                        match token.typ {
+                            TokenType::EqualEqual => OpCode::Equal,
+                            TokenType::BangEqual => OpCode::NotEqual,
+                            TokenType::Greater => OpCode::Greater,
+                            TokenType::GreaterEqual => OpCode::GreaterEqual,
+                            TokenType::Less => OpCode::Less,
+                            TokenType::LessEqual => OpCode::LessEqual,
+                            _ => unreachable!("Only comparison operators reach this arm"),
+                        },
+                        token.line,
+                    );
+                }
+                TokenType::Amp | TokenType::Pipe | TokenType::Caret => {
+                    return Err(CompilerError::UnsupportedOperator { token });
+                }
                 _ => {
                     return Err(CompilerError::UnexpectedToken {
                         expected: TokenType::Number(0.0),
@@ -133,11 +192,158 @@ where
     }
 }
 
+/// Compile a whole program, which returns `nil` when it finishes.
 pub fn compile<'s, I>(tokens: I) -> CompileResult<'s>
 where
     I: IntoIterator<Item = &'s Token<'s>>,
 {
-    let mut compiler = Compiler::from(tokens.into_iter());
-    compiler.expression()?;
+    // Comments can appear anywhere, but never mean anything to the compiler.
+    let mut compiler = Compiler::from(
+        tokens
+            .into_iter()
+            .filter(|t| !matches!(t.typ, TokenType::Comment(_))),
+    );
+    while compiler.tokens.peek().is_some() {
+        compiler.statement()?;
+    }
+
+    let line = compiler.chunk.lines.last().copied().unwrap_or(1);
+    compiler.chunk.write(OpCode::Nil, line);
+    compiler.chunk.write(OpCode::Return, line);
     Ok(compiler.chunk)
 }
+
+/// Compile a single bare expression (with no trailing semicolon) that must use up all of the tokens.
+/// The caller decides what to do with the value it leaves on the stack.
+pub fn compile_expression<'s, I>(tokens: I) -> CompileResult<'s>
+where
+    I: IntoIterator<Item = &'s Token<'s>>,
+{
+    let mut compiler = Compiler::from(
+        tokens
+            .into_iter()
+            .filter(|t| !matches!(t.typ, TokenType::Comment(_))),
+    );
+    compiler.expression()?;
+
+    match compiler.tokens.next() {
+        None => Ok(compiler.chunk),
+        Some(token) => Err(CompilerError::UnexpectedToken {
+            expected: TokenType::Semicolon,
+            token,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{borrow::Cow, io};
+
+    use itertools::Itertools;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        bytecode::virtual_machine::{RuntimeError, VirtualMachine},
+        shared::scanner::scan,
+    };
+
+    #[rstest]
+    #[case("1 + 2", Ok(Value::Number(3.0)))]
+    #[case("1 + /* hi */ 2", Ok(Value::Number(3.0)))]
+    #[case("/* leading */ 2 * 3", Ok(Value::Number(6.0)))]
+    #[case("(4 - 1) // trailing", Ok(Value::Number(3.0)))]
+    #[case("-/* odd\nbut legal */5", Ok(Value::Number(-5.0)))]
+    #[case("1 < 2", Ok(Value::Boolean(true)))]
+    #[case("2 < 1", Ok(Value::Boolean(false)))]
+    #[case("2 == 3", Ok(Value::Boolean(false)))]
+    #[case("3 == 3", Ok(Value::Boolean(true)))]
+    #[case("2 != 3", Ok(Value::Boolean(true)))]
+    #[case("1 <= 1", Ok(Value::Boolean(true)))]
+    #[case("2 <= 1", Ok(Value::Boolean(false)))]
+    #[case("1 >= 2", Ok(Value::Boolean(false)))]
+    #[case("2 >= 2", Ok(Value::Boolean(true)))]
+    #[case("3 > 2", Ok(Value::Boolean(true)))]
+    #[case("1 + 2 == 3", Ok(Value::Boolean(true)))]
+    #[case("1 < 2 == true", Ok(Value::Boolean(true)))]
+    #[case("nil == false", Ok(Value::Boolean(false)))]
+    #[case("true != false", Ok(Value::Boolean(true)))]
+    #[case("0 / 0 >= 0", Ok(Value::Boolean(false)))]
+    #[case("0 / 0 <= 0", Ok(Value::Boolean(false)))]
+    #[case("0 / 0 != 0 / 0", Ok(Value::Boolean(true)))]
+    #[case("!true", Ok(Value::Boolean(false)))]
+    #[case("!nil", Ok(Value::Boolean(true)))]
+    #[case("!0", Ok(Value::Boolean(false)))]
+    #[case("!!nil", Ok(Value::Boolean(false)))]
+    #[case("!(1 < 2)", Ok(Value::Boolean(false)))]
+    #[case("!false == true", Ok(Value::Boolean(true)))]
+    #[case("true", Ok(Value::Boolean(true)))]
+    #[case("false", Ok(Value::Boolean(false)))]
+    #[case("nil", Ok(Value::Nil))]
+    #[case("\"hello\"", Ok(Value::String(Cow::from("hello"))))]
+    #[case("\"a\" + \"b\"", Ok(Value::String(Cow::from("ab"))))]
+    #[case("\"a\" + \"b\" + \"c\"", Ok(Value::String(Cow::from("abc"))))]
+    #[case("\"ab\" == \"a\" + \"b\"", Ok(Value::Boolean(true)))]
+    #[case("(true)", Ok(Value::Boolean(true)))]
+    #[case("-true", Err(RuntimeError::Unimplemented { msg: "Unary operation not implemented: -Boolean(true)".into() }))]
+    #[case("-nil", Err(RuntimeError::Unimplemented { msg: "Unary operation not implemented: -Nil".into() }))]
+    #[case("1 < nil", Err(RuntimeError::Unimplemented { msg: "Binary operation not implemented: Number(1.0) < Nil".into() }))]
+    #[case("nil >= 1", Err(RuntimeError::Unimplemented { msg: "Binary operation not implemented: Nil >= Number(1.0)".into() }))]
+    #[case("\"a\" + 1", Err(RuntimeError::Unimplemented { msg: "Binary operation not implemented: String(\"a\") + Number(1.0)".into() }))]
+    fn test_expressions(#[case] source: &str, #[case] expected: Result<Value, RuntimeError>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let mut chunk = compile_expression(tokens.iter()).unwrap();
+        chunk.write(OpCode::Return, 0);
+
+        assert_eq!(
+            VirtualMachine::new(io::sink()).interpret(&chunk, None),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("print 1 + 2;", "3\n")]
+    #[case("print \"a\" + \"b\"; print !nil;", "ab\ntrue\n")]
+    #[case("print 1; // done\nprint 2;", "1\n2\n")]
+    #[case("", "")]
+    fn test_print(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let chunk = compile(tokens.iter()).unwrap();
+
+        let mut output = Vec::new();
+        let result = VirtualMachine::new(&mut output).interpret(&chunk, None);
+
+        assert_eq!(result, Ok(Value::Nil));
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("print 1", "Unexpected end of input")]
+    #[case("print 1 2;", "Expected ; on line 1, column 9, but got number(2)")]
+    #[case("print 1, 2;", "Expected ; on line 1, column 8, but got ,")]
+    #[case("1 + 2", "Unexpected end of input")]
+    fn test_statements_need_semicolons(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+
+        assert_eq!(compile(tokens.iter()).unwrap_err().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "print 1 & 2;",
+        "The & operator on line 1, column 9 is only supported by the tree-walker"
+    )]
+    #[case(
+        "print 1 | 2;",
+        "The | operator on line 1, column 9 is only supported by the tree-walker"
+    )]
+    #[case(
+        "print 1 ^ 2;",
+        "The ^ operator on line 1, column 9 is only supported by the tree-walker"
+    )]
+    fn test_bitwise_operators_are_unsupported(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+
+        assert_eq!(compile(tokens.iter()).unwrap_err().to_string(), expected);
+    }
+}
@@ -1,12 +1,15 @@
-use std::{io, io::Write};
+use std::{io, io::Write, path::Path};
 
 use anyhow::Result;
 use colored::Colorize;
 use itertools::Itertools;
 
 use crate::{
-    bytecode::{ops::OpCode, virtual_machine::VirtualMachine},
-    shared::scanner,
+    bytecode::{
+        ops::{Chunk, OpCode},
+        virtual_machine::VirtualMachine,
+    },
+    shared::{scanner, scanner::Token},
     walker::InterpreterError,
 };
 
@@ -15,13 +18,32 @@ mod ops;
 pub mod values;
 mod virtual_machine;
 
-pub fn exec(source: &str) -> Result<()> {
-    interpret(source)?;
+pub use virtual_machine::DEFAULT_STACK_MAX;
+
+pub fn exec(source: &str, stack_max: usize) -> Result<()> {
+    interpret(source, stack_max)?;
+
+    Ok(())
+}
+
+/// Compile a script and save its bytecode to `output`, to be run later by [`exec_compiled`].
+pub fn compile_to_file(source: &str, output: &Path) -> Result<()> {
+    let tokens = scan(source)?;
+    let chunk = compile(&tokens)?;
+    std::fs::write(output, chunk.to_bytes())?;
+
+    Ok(())
+}
+
+/// Run bytecode saved by [`compile_to_file`].
+pub fn exec_compiled(bytes: &[u8], stack_max: usize) -> Result<()> {
+    let chunk = Chunk::from_bytes(bytes)?;
+    run(&chunk, stack_max)?;
 
     Ok(())
 }
 
-pub fn repl() -> Result<()> {
+pub fn repl(stack_max: usize) -> Result<()> {
     println!("Gejang VM REPL");
 
     let stdin = io::stdin();
@@ -33,14 +55,21 @@ pub fn repl() -> Result<()> {
         let mut buffer = String::new();
         stdin.read_line(&mut buffer)?;
 
-        match interpret(&buffer) {
+        match interpret(&buffer, stack_max) {
             Ok(_) => {}
             Err(e) => eprintln!("{e}"),
         }
     }
 }
 
-fn interpret(source: &str) -> Result<(), InterpreterError> {
+fn interpret(source: &str, stack_max: usize) -> Result<(), InterpreterError> {
+    let tokens = scan(source)?;
+    let chunk = compile(&tokens)?;
+
+    run(&chunk, stack_max)
+}
+
+fn scan(source: &str) -> Result<Vec<Token<'_>>, InterpreterError> {
     let (tokens, errors): (Vec<_>, Vec<_>) = scanner::scan(source).partition_result();
 
     if !errors.is_empty() {
@@ -50,18 +79,30 @@ fn interpret(source: &str) -> Result<(), InterpreterError> {
         return Err(InterpreterError::Scanner);
     }
 
-    let mut chunk = compiler::compile(tokens.iter()).map_err(|e| {
-        eprintln!("{}", e.to_string().red());
-        InterpreterError::Evaluation
-    })?;
+    Ok(tokens)
+}
 
-    chunk.write(OpCode::Return, 0); // TODO: Remove this
+fn compile<'s>(tokens: &'s [Token<'s>]) -> Result<Chunk<'s>, InterpreterError> {
+    // A lone expression is run for its value, which gets shown after it runs.
+    Ok(match compiler::compile_expression(tokens.iter()) {
+        Ok(mut chunk) => {
+            chunk.write(OpCode::Return, chunk.lines.last().copied().unwrap_or(1));
+            chunk
+        }
+        Err(_) => compiler::compile(tokens.iter()).map_err(|e| {
+            eprintln!("{}", e.to_string().red());
+            InterpreterError::Evaluation
+        })?,
+    })
+}
 
-    println!("{}", chunk.to_string().dimmed());
+fn run(chunk: &Chunk, stack_max: usize) -> Result<(), InterpreterError> {
+    // Tracing goes to stderr so that it doesn't get mixed into the program's output.
+    eprintln!("{}", chunk.to_string().dimmed());
 
-    let mut vm = VirtualMachine::new();
+    let mut vm = VirtualMachine::new(io::stdout()).with_stack_max(stack_max);
 
-    vm.interpret(&chunk, true)
+    vm.interpret(chunk, Some(&mut io::stderr()))
         .map_err(|e| {
             eprintln!("{}", e.to_string().red());
             InterpreterError::Evaluation
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{borrow::Cow, fmt::Display};
 
 use anyhow::{bail, Result};
 use itertools::Itertools;
@@ -8,15 +8,103 @@ use crate::bytecode::values::Value;
 
 #[derive(Debug, AsRefStr, IntoStaticStr)]
 pub enum OpCode {
-    Constant { index: usize },
+    Constant {
+        index: usize,
+    },
+    /// Like `Constant`, for indexes that wouldn't fit in the single byte the short form gets in the book.
+    ConstantLong {
+        index: usize,
+    },
+    True,
+    False,
+    Nil,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
     Add,
     Subtract,
     Multiply,
     Divide,
     Negate,
+    Not,
+    Print,
+    Pop,
     Return,
 }
 
+impl OpCode {
+    /// The byte that stands for this instruction in a serialized chunk.
+    fn tag(&self) -> u8 {
+        match self {
+            OpCode::Constant { .. } => 0,
+            OpCode::ConstantLong { .. } => 1,
+            OpCode::True => 2,
+            OpCode::False => 3,
+            OpCode::Nil => 4,
+            OpCode::Equal => 5,
+            OpCode::Greater => 6,
+            OpCode::Less => 7,
+            OpCode::Add => 8,
+            OpCode::Subtract => 9,
+            OpCode::Multiply => 10,
+            OpCode::Divide => 11,
+            OpCode::Negate => 12,
+            OpCode::Not => 13,
+            OpCode::Print => 14,
+            OpCode::Pop => 15,
+            OpCode::Return => 16,
+            OpCode::NotEqual => 17,
+            OpCode::GreaterEqual => 18,
+            OpCode::LessEqual => 19,
+        }
+    }
+}
+
+/// Serialized chunks start with these bytes, so that other files are rejected up front.
+const MAGIC: &[u8] = b"GJBC";
+/// Bump this whenever the serialized layout (or the meaning of an opcode tag) changes.
+const FORMAT_VERSION: u8 = 1;
+
+fn write_u32(bytes: &mut Vec<u8>, n: usize) {
+    bytes.extend(
+        u32::try_from(n)
+            .expect("Chunk is too big to serialize")
+            .to_le_bytes(),
+    );
+}
+
+/// Reads a serialized chunk from front to back, failing instead of panicking if it runs out early.
+struct Reader<'b> {
+    bytes: &'b [u8],
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, n: usize) -> Result<&'b [u8]> {
+        if self.bytes.len() < n {
+            bail!("Chunk ended unexpectedly")
+        }
+
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?) as usize)
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Chunk<'s> {
     pub code: Vec<OpCode>,
@@ -25,7 +113,6 @@ pub struct Chunk<'s> {
 }
 
 impl<'s> Chunk<'s> {
-    #[allow(dead_code)]
     pub fn new(
         code: Vec<OpCode>,
         constants: Vec<Value<'s>>,
@@ -44,15 +131,145 @@ impl<'s> Chunk<'s> {
 
     pub fn add_constant(&mut self, value: Value<'s>, line: usize) {
         self.constants.push(value);
-        self.code.push(OpCode::Constant {
-            index: self.constants.len() - 1,
-        });
-        self.lines.push(line)
+        let index = self.constants.len() - 1;
+        self.write(
+            if index <= u8::MAX as usize {
+                OpCode::Constant { index }
+            } else {
+                OpCode::ConstantLong { index }
+            },
+            line,
+        );
+    }
+
+    /// Serialize the chunk, to be loaded again by [`Chunk::from_bytes`].
+    ///
+    /// All integers are little-endian. After the magic bytes and format version come the constants
+    /// (a count, then a tag byte and payload for each), then the instructions (a count, then a tag byte,
+    /// any operand, and the line for each).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(FORMAT_VERSION);
+
+        write_u32(&mut bytes, self.constants.len());
+        for constant in &self.constants {
+            match constant {
+                Value::Number(n) => {
+                    bytes.push(0);
+                    bytes.extend(n.to_le_bytes());
+                }
+                Value::String(s) => {
+                    bytes.push(1);
+                    write_u32(&mut bytes, s.len());
+                    bytes.extend(s.as_bytes());
+                }
+                Value::Boolean(b) => bytes.extend([2, *b as u8]),
+                Value::Nil => bytes.push(3),
+                Value::Object(_) => unreachable!("The compiler never makes object constants"),
+            }
+        }
+
+        write_u32(&mut bytes, self.code.len());
+        for (op, line) in self.code.iter().zip(&self.lines) {
+            bytes.push(op.tag());
+            match op {
+                OpCode::Constant { index } => bytes.push(*index as u8),
+                OpCode::ConstantLong { index } => write_u32(&mut bytes, *index),
+                _ => {}
+            }
+            write_u32(&mut bytes, *line);
+        }
+
+        bytes
     }
 
+    /// Load a chunk serialized by [`Chunk::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Chunk<'s>> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC) {
+            bail!("Not a serialized chunk")
+        }
+        let version = reader.u8()?;
+        if version != FORMAT_VERSION {
+            bail!("Unsupported chunk format version {version}, expected {FORMAT_VERSION}")
+        }
+
+        let mut constants = Vec::new();
+        for _ in 0..reader.u32()? {
+            constants.push(match reader.u8()? {
+                0 => Value::Number(reader.f64()?),
+                1 => {
+                    let len = reader.u32()?;
+                    Value::String(Cow::from(String::from_utf8(reader.take(len)?.to_vec())?))
+                }
+                2 => Value::Boolean(reader.u8()? != 0),
+                3 => Value::Nil,
+                tag => bail!("Unknown constant tag {tag}"),
+            });
+        }
+
+        let mut code = Vec::new();
+        let mut lines = Vec::new();
+        for _ in 0..reader.u32()? {
+            let op = match reader.u8()? {
+                0 => OpCode::Constant {
+                    index: reader.u8()? as usize,
+                },
+                1 => OpCode::ConstantLong {
+                    index: reader.u32()?,
+                },
+                2 => OpCode::True,
+                3 => OpCode::False,
+                4 => OpCode::Nil,
+                5 => OpCode::Equal,
+                6 => OpCode::Greater,
+                7 => OpCode::Less,
+                8 => OpCode::Add,
+                9 => OpCode::Subtract,
+                10 => OpCode::Multiply,
+                11 => OpCode::Divide,
+                12 => OpCode::Negate,
+                13 => OpCode::Not,
+                14 => OpCode::Print,
+                15 => OpCode::Pop,
+                16 => OpCode::Return,
+                17 => OpCode::NotEqual,
+                18 => OpCode::GreaterEqual,
+                19 => OpCode::LessEqual,
+                tag => bail!("Unknown opcode tag {tag}"),
+            };
+            // The VM trusts constant indexes, so check them here rather than letting it panic later.
+            if let OpCode::Constant { index } | OpCode::ConstantLong { index } = op {
+                if index >= constants.len() {
+                    bail!(
+                        "Constant index {index} is out of range for {} constants",
+                        constants.len()
+                    )
+                }
+            }
+            code.push(op);
+            lines.push(reader.u32()?);
+        }
+
+        if !reader.bytes.is_empty() {
+            bail!("Unexpected data after the end of the chunk")
+        }
+
+        Chunk::new(code, constants, lines)
+    }
+
+    /// All instructions must go through here so that `code` and `lines` never drift apart,
+    /// which would make `fmt_instruction` fail to find the line for a valid offset.
     pub fn write(&mut self, op: OpCode, line: usize) {
         self.code.push(op);
         self.lines.push(line);
+
+        debug_assert_eq!(
+            self.code.len(),
+            self.lines.len(),
+            "Chunk code and lines drifted apart"
+        );
     }
 
     pub fn fmt_instruction(&self, offset: usize) -> Option<String> {
@@ -80,9 +297,23 @@ impl<'s> Chunk<'s> {
             OpCode::Negate => {
                 format!("{offset:04} {line:04} {o}")
             }
-            OpCode::Constant { index } => {
+            OpCode::Constant { index } | OpCode::ConstantLong { index } => {
                 format!("{offset:04} {line:04} {o} {:?}", self.constants[*index])
             }
+            OpCode::Equal
+            | OpCode::NotEqual
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Not
+            | OpCode::Print
+            | OpCode::Pop => {
+                format!("{offset:04} {line:04} {o}")
+            }
+            OpCode::True | OpCode::False | OpCode::Nil => {
+                format!("{offset:04} {line:04} {o}")
+            }
         })
     }
 }
@@ -98,3 +329,117 @@ impl Display for Chunk<'_> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        bytecode::{compiler::compile, virtual_machine::VirtualMachine},
+        shared::scanner::scan,
+    };
+
+    #[test]
+    fn test_code_and_lines_stay_in_sync() {
+        let mut chunk = Chunk::default();
+
+        chunk.add_constant(Value::Number(1.0), 1);
+        chunk.add_constant(Value::Number(2.0), 1);
+        chunk.write(OpCode::Add, 1);
+        chunk.write(OpCode::Negate, 2);
+        chunk.add_constant(Value::Number(3.0), 3);
+        chunk.write(OpCode::Return, 3);
+
+        assert_eq!(chunk.code.len(), chunk.lines.len());
+        assert!((0..chunk.code.len()).all(|offset| chunk.fmt_instruction(offset).is_some()));
+    }
+
+    #[test]
+    fn test_many_constants_use_the_long_form() {
+        let mut chunk = Chunk::default();
+        for n in 0..300 {
+            chunk.add_constant(Value::Number(n as f64), 1);
+        }
+
+        assert!(matches!(chunk.code[255], OpCode::Constant { index: 255 }));
+        assert!(matches!(
+            chunk.code[256],
+            OpCode::ConstantLong { index: 256 }
+        ));
+        assert_eq!(
+            chunk.fmt_instruction(298),
+            Some("0298 0001 CONSTANTLONG Number(298.0)".to_string())
+        );
+
+        let chunk = Chunk::new(
+            vec![OpCode::ConstantLong { index: 299 }, OpCode::Return],
+            chunk.constants,
+            vec![1, 1],
+        )
+        .unwrap();
+        assert_eq!(
+            VirtualMachine::new(std::io::sink()).interpret(&chunk, None),
+            Ok(Value::Number(299.0))
+        );
+    }
+
+    #[test]
+    fn test_new_rejects_mismatched_lengths() {
+        assert!(Chunk::new(vec![OpCode::Return], vec![], vec![]).is_err());
+        assert!(Chunk::new(vec![OpCode::Return], vec![], vec![1]).is_ok());
+    }
+
+    #[rstest]
+    #[case("print 1 + 2;")]
+    #[case("print \"hello\" + \" world\";\n!(1 < 2) == false;")]
+    #[case("print 1 <= 2 == !(2 < 1);")]
+    #[case("print -1.5 * 2 >= 3 != nil;\n\n\nprint \"🦀\";")]
+    #[case("")]
+    fn test_serialization_round_trips(#[case] source: &str) {
+        let tokens: Vec<_> = scan(source).try_collect().unwrap();
+        let chunk = compile(tokens.iter()).unwrap();
+
+        let loaded = Chunk::from_bytes(&chunk.to_bytes()).unwrap();
+
+        assert_eq!(loaded.to_string(), chunk.to_string());
+        assert_eq!(loaded.lines, chunk.lines);
+        assert_eq!(loaded.constants, chunk.constants);
+    }
+
+    #[test]
+    fn test_serialization_round_trips_long_constants() {
+        let mut chunk = Chunk::default();
+        for n in 0..300 {
+            chunk.add_constant(Value::Number(n as f64), n);
+        }
+        chunk.add_constant(Value::Boolean(true), 300);
+        chunk.add_constant(Value::Nil, 300);
+
+        let loaded = Chunk::from_bytes(&chunk.to_bytes()).unwrap();
+
+        assert_eq!(loaded.to_string(), chunk.to_string());
+    }
+
+    #[rstest]
+    #[case(b"", "Not a serialized chunk")]
+    #[case(b"#!/usr/bin/env gejang", "Not a serialized chunk")]
+    #[case(b"GJBC\x02", "Unsupported chunk format version 2, expected 1")]
+    #[case(b"GJBC\x01\x01\x00\x00", "Chunk ended unexpectedly")]
+    #[case(b"GJBC\x01\x01\x00\x00\x00\x09", "Unknown constant tag 9")]
+    #[case(
+        b"GJBC\x01\x00\x00\x00\x00\x01\x00\x00\x00\x63\x01\x00\x00\x00",
+        "Unknown opcode tag 99"
+    )]
+    #[case(
+        b"GJBC\x01\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x01\x00\x00\x00",
+        "Constant index 0 is out of range for 0 constants"
+    )]
+    #[case(
+        b"GJBC\x01\x00\x00\x00\x00\x00\x00\x00\x00\xff",
+        "Unexpected data after the end of the chunk"
+    )]
+    fn test_from_bytes_rejects_malformed_chunks(#[case] bytes: &[u8], #[case] expected: &str) {
+        assert_eq!(Chunk::from_bytes(bytes).unwrap_err().to_string(), expected);
+    }
+}
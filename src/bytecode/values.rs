@@ -6,7 +6,7 @@ use std::{
 
 use strum_macros::{AsRefStr, IntoStaticStr};
 
-use crate::shared::scanner::TokenType;
+use crate::shared::{numbers::format_number, scanner::TokenType};
 
 #[derive(Debug, Clone, PartialEq, AsRefStr, IntoStaticStr)]
 pub enum Value<'s> {
@@ -23,7 +23,7 @@ impl<'s> From<&TokenType<'s>> for Value<'s> {
     fn from(token: &TokenType<'s>) -> Self {
         match token {
             TokenType::Number(value) => Value::Number(*value),
-            TokenType::String(value) => Value::String(Cow::from(*value)),
+            TokenType::String(value) => Value::String(value.clone()),
             TokenType::True => Value::Boolean(true),
             TokenType::False => Value::Boolean(false),
             TokenType::Nil => Value::Nil,
@@ -50,7 +50,7 @@ impl Display for Value<'_> {
             "{}",
             match self {
                 Value::Object(_) => "<object>".to_string(), // TODO: implement better object display
-                Value::Number(value) => value.to_string(),
+                Value::Number(value) => format_number(*value),
                 Value::String(value) => value.to_string(),
                 Value::Boolean(value) => value.to_string(),
                 Value::Nil => "nil".to_string(),
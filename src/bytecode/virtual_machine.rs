@@ -1,3 +1,5 @@
+use std::{borrow::Cow, io::Write};
+
 use colored::Colorize;
 use itertools::Itertools;
 use thiserror::Error;
@@ -13,26 +15,92 @@ pub enum RuntimeError {
     Unimplemented { msg: String },
     #[error("Invalid instruction pointer: {ip}")]
     InvalidInstructionPointer { ip: usize },
+    #[error("Stack underflow at instruction {ip}")]
+    StackUnderflow { ip: usize },
+    #[error("Stack overflow at instruction {ip}")]
+    StackOverflow { ip: usize },
+    #[error("Print failed")]
+    PrintFailed,
+    #[error("Trace failed")]
+    TraceFailed,
 }
 
 pub type EvaluationResult<'s> = Result<Value<'s>, RuntimeError>;
 
-pub struct VirtualMachine<'s> {
-    #[allow(dead_code)]
+/// How many values the stack can hold, unless the machine is told otherwise.
+pub const DEFAULT_STACK_MAX: usize = 256;
+
+pub struct VirtualMachine<'s, W: Write> {
     stack: Vec<Value<'s>>, // Book uses a fixed-size stack
+    /// How many values the stack can hold before it overflows.
+    stack_max: usize,
+    /// Where `print` statements write to.
+    output: W,
 }
 
-impl<'s> VirtualMachine<'s> {
-    pub fn new() -> Self {
-        VirtualMachine { stack: Vec::new() }
+impl<'s, W: Write> VirtualMachine<'s, W> {
+    pub fn new(output: W) -> Self {
+        VirtualMachine {
+            stack: Vec::new(),
+            stack_max: DEFAULT_STACK_MAX,
+            output,
+        }
+    }
+
+    /// Let the stack hold up to `stack_max` values instead of `DEFAULT_STACK_MAX`.
+    pub fn with_stack_max(mut self, stack_max: usize) -> Self {
+        self.stack_max = stack_max;
+        self
+    }
+
+    /// A malformed chunk could try to pop more values than it pushed,
+    /// which should be an error rather than a panic.
+    fn pop(&mut self, ip: usize) -> Result<Value<'s>, RuntimeError> {
+        self.stack.pop().ok_or(RuntimeError::StackUnderflow { ip })
+    }
+
+    /// Replace the top two values, which must be numbers, with the result of comparing them.
+    fn compare(
+        &mut self,
+        ip: usize,
+        symbol: &str,
+        op: fn(&f64, &f64) -> bool,
+    ) -> Result<(), RuntimeError> {
+        let b = self.pop(ip)?;
+        let a = self.pop(ip)?;
+        self.stack.push(match (&a, &b) {
+            (Value::Number(a), Value::Number(b)) => Value::Boolean(op(a, b)),
+            _ => {
+                return Err(RuntimeError::Unimplemented {
+                    msg: format!("Binary operation not implemented: {:?} {symbol} {:?}", a, b),
+                })
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Like the book's fixed-size stack, refuse to grow past `stack_max`.
+    /// Only instructions that leave the stack bigger than they found it need to go through here.
+    fn push(&mut self, ip: usize, value: Value<'s>) -> Result<(), RuntimeError> {
+        if self.stack.len() >= self.stack_max {
+            return Err(RuntimeError::StackOverflow { ip });
+        }
+
+        self.stack.push(value);
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn interpret(&mut self, chunk: &Chunk<'s>, trace: bool) -> EvaluationResult<'s> {
+    /// Run the chunk, describing each instruction (and the stack it sees) to `trace` if given.
+    pub fn interpret(
+        &mut self,
+        chunk: &Chunk<'s>,
+        mut trace: Option<&mut dyn Write>,
+    ) -> EvaluationResult<'s> {
         let mut ip = 0;
 
         loop {
-            if trace {
+            if let Some(sink) = trace.as_deref_mut() {
                 let instruction = chunk
                     .fmt_instruction(ip)
                     .ok_or(RuntimeError::InvalidInstructionPointer { ip })?;
@@ -52,21 +120,55 @@ impl<'s> VirtualMachine<'s> {
                         format!("\n│ {}", s)
                     }
                 };
-                println!(
+                writeln!(
+                    sink,
                     "{}",
                     format!("┌─ {}{}\n└──────────────────────", instruction, stack).dimmed()
-                );
+                )
+                .map_err(|_| RuntimeError::TraceFailed)?;
             }
 
-            match chunk.code[ip] {
+            let op = chunk
+                .code
+                .get(ip)
+                .ok_or(RuntimeError::InvalidInstructionPointer { ip })?;
+
+            match *op {
                 OpCode::Return => {
-                    return Ok(self.stack.pop().expect("Popped from empty stack"));
+                    return self.pop(ip);
+                }
+                OpCode::Equal | OpCode::NotEqual => {
+                    let b = self.pop(ip)?;
+                    let a = self.pop(ip)?;
+                    // Values of different types are never equal.
+                    self.stack
+                        .push(Value::Boolean((a == b) == matches!(op, OpCode::Equal)));
+                    ip += 1;
+                }
+                OpCode::Greater => {
+                    self.compare(ip, ">", |a, b| a > b)?;
+                    ip += 1;
+                }
+                OpCode::GreaterEqual => {
+                    self.compare(ip, ">=", |a, b| a >= b)?;
+                    ip += 1;
+                }
+                OpCode::Less => {
+                    self.compare(ip, "<", |a, b| a < b)?;
+                    ip += 1;
+                }
+                OpCode::LessEqual => {
+                    self.compare(ip, "<=", |a, b| a <= b)?;
+                    ip += 1;
                 }
                 OpCode::Add => {
-                    let b = self.stack.pop().expect("Popped from empty stack");
-                    let a = self.stack.pop().expect("Popped from empty stack");
+                    let b = self.pop(ip)?;
+                    let a = self.pop(ip)?;
                     self.stack.push(match (&a, &b) {
                         (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                        (Value::String(a), Value::String(b)) => {
+                            Value::String(Cow::from(format!("{a}{b}")))
+                        }
                         _ => {
                             return Err(RuntimeError::Unimplemented {
                                 msg: format!("Binary operation not implemented: {:?} + {:?}", a, b),
@@ -76,8 +178,8 @@ impl<'s> VirtualMachine<'s> {
                     ip += 1;
                 }
                 OpCode::Subtract => {
-                    let b = self.stack.pop().expect("Popped from empty stack");
-                    let a = self.stack.pop().expect("Popped from empty stack");
+                    let b = self.pop(ip)?;
+                    let a = self.pop(ip)?;
                     self.stack.push(match (&a, &b) {
                         (Value::Number(a), Value::Number(b)) => Value::Number(a - b),
                         _ => {
@@ -89,8 +191,8 @@ impl<'s> VirtualMachine<'s> {
                     ip += 1;
                 }
                 OpCode::Multiply => {
-                    let b = self.stack.pop().expect("Popped from empty stack");
-                    let a = self.stack.pop().expect("Popped from empty stack");
+                    let b = self.pop(ip)?;
+                    let a = self.pop(ip)?;
                     self.stack.push(match (&a, &b) {
                         (Value::Number(a), Value::Number(b)) => Value::Number(a * b),
                         _ => {
@@ -102,8 +204,8 @@ impl<'s> VirtualMachine<'s> {
                     ip += 1;
                 }
                 OpCode::Divide => {
-                    let b = self.stack.pop().expect("Popped from empty stack");
-                    let a = self.stack.pop().expect("Popped from empty stack");
+                    let b = self.pop(ip)?;
+                    let a = self.pop(ip)?;
                     self.stack.push(match (&a, &b) {
                         (Value::Number(a), Value::Number(b)) => Value::Number(a / b),
                         _ => {
@@ -116,19 +218,46 @@ impl<'s> VirtualMachine<'s> {
                 }
 
                 OpCode::Negate => {
-                    let value = self.stack.pop().expect("Popped from empty stack");
+                    let value = self.pop(ip)?;
                     self.stack.push(match value {
                         Value::Number(ref value) => Value::Number(-value),
                         _ => {
                             return Err(RuntimeError::Unimplemented {
-                                msg: "Cannot negate non-number".into(),
+                                msg: format!("Unary operation not implemented: -{:?}", value),
                             })
                         }
                     });
                     ip += 1;
                 }
-                OpCode::Constant { index } => {
-                    self.stack.push(chunk.constants[index].clone()); // TODO: clone here, can we use COW?
+                OpCode::Print => {
+                    let value = self.pop(ip)?;
+                    writeln!(self.output, "{value}").map_err(|_| RuntimeError::PrintFailed)?;
+                    ip += 1;
+                }
+                OpCode::Pop => {
+                    self.pop(ip)?;
+                    ip += 1;
+                }
+                OpCode::Not => {
+                    let value = self.pop(ip)?;
+                    self.stack.push(Value::Boolean(!value.is_truthy()));
+                    ip += 1;
+                }
+                OpCode::Constant { index } | OpCode::ConstantLong { index } => {
+                    // TODO: clone here, can we use COW?
+                    self.push(ip, chunk.constants[index].clone())?;
+                    ip += 1;
+                }
+                OpCode::True => {
+                    self.push(ip, Value::Boolean(true))?;
+                    ip += 1;
+                }
+                OpCode::False => {
+                    self.push(ip, Value::Boolean(false))?;
+                    ip += 1;
+                }
+                OpCode::Nil => {
+                    self.push(ip, Value::Nil)?;
                     ip += 1;
                 }
             }
@@ -137,4 +266,113 @@ impl<'s> VirtualMachine<'s> {
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use itertools::Itertools;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        bytecode::compiler::compile,
+        shared::scanner::{scan, Token},
+    };
+
+    #[rstest]
+    #[case(
+        vec![OpCode::Constant { index: 0 }, OpCode::Add, OpCode::Return],
+        Err(RuntimeError::StackUnderflow { ip: 1 })
+    )]
+    #[case(vec![OpCode::Add], Err(RuntimeError::StackUnderflow { ip: 0 }))]
+    #[case(vec![OpCode::Negate], Err(RuntimeError::StackUnderflow { ip: 0 }))]
+    #[case(
+        vec![OpCode::Constant { index: 0 }, OpCode::Less, OpCode::Return],
+        Err(RuntimeError::StackUnderflow { ip: 1 })
+    )]
+    #[case(vec![OpCode::Return], Err(RuntimeError::StackUnderflow { ip: 0 }))]
+    #[case(vec![OpCode::Pop], Err(RuntimeError::StackUnderflow { ip: 0 }))]
+    #[case(
+        vec![OpCode::Constant { index: 0 }],
+        Err(RuntimeError::InvalidInstructionPointer { ip: 1 })
+    )]
+    #[case(
+        vec![OpCode::Constant { index: 0 }, OpCode::Negate, OpCode::Return],
+        Ok(Value::Number(-1.0))
+    )]
+    fn test_malformed_chunks(#[case] code: Vec<OpCode>, #[case] expected: EvaluationResult) {
+        let lines = vec![1; code.len()];
+        let chunk = Chunk::new(code, vec![Value::Number(1.0)], lines).unwrap();
+
+        assert_eq!(
+            VirtualMachine::new(std::io::sink()).interpret(&chunk, None),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("1 + 2; 3 + 4;", "")]
+    #[case("1; print 2; 3;", "2\n")]
+    #[case("\"a\" + \"b\"; !nil;", "")]
+    fn test_expression_statements_leave_an_empty_stack(
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let chunk = compile(tokens.iter()).unwrap();
+
+        let mut output = Vec::new();
+        let mut vm = VirtualMachine::new(&mut output);
+        assert_eq!(vm.interpret(&chunk, None), Ok(Value::Nil));
+        assert!(vm.stack.is_empty());
+        drop(vm);
+
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let code: Vec<_> = (0..=DEFAULT_STACK_MAX).map(|_| OpCode::Nil).collect();
+        let lines = vec![1; code.len()];
+        let chunk = Chunk::new(code, vec![], lines).unwrap();
+
+        assert_eq!(
+            VirtualMachine::new(std::io::sink()).interpret(&chunk, None),
+            Err(RuntimeError::StackOverflow {
+                ip: DEFAULT_STACK_MAX
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(2, Err(RuntimeError::StackOverflow { ip: 2 }))]
+    #[case(3, Ok(Value::Nil))]
+    #[case(DEFAULT_STACK_MAX * 2, Ok(Value::Nil))]
+    fn test_stack_max_can_be_changed(#[case] stack_max: usize, #[case] expected: EvaluationResult) {
+        let code = vec![OpCode::Nil, OpCode::Nil, OpCode::Nil, OpCode::Return];
+        let lines = vec![1; code.len()];
+        let chunk = Chunk::new(code, vec![], lines).unwrap();
+
+        assert_eq!(
+            VirtualMachine::new(std::io::sink())
+                .with_stack_max(stack_max)
+                .interpret(&chunk, None),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_trace_goes_to_the_sink() {
+        let tokens: Vec<Token> = scan("print 1 + 2;").try_collect().unwrap();
+        let chunk = compile(tokens.iter()).unwrap();
+
+        let mut output = Vec::new();
+        let mut trace = Vec::new();
+        VirtualMachine::new(&mut output)
+            .interpret(&chunk, Some(&mut trace))
+            .unwrap();
+
+        let trace = String::from_utf8(trace).unwrap();
+        assert!(trace.contains("0000 0001 CONSTANT Number(1.0)"), "{trace}");
+        assert!(trace.contains("0002 0001 ADD"), "{trace}");
+        assert!(trace.contains("0 -> Number(3.0)"), "{trace}");
+        assert_eq!(String::from_utf8(output).unwrap(), "3\n");
+    }
+}
@@ -0,0 +1,26 @@
+#![cfg_attr(test, feature(test))]
+
+//! Gejang is an interpreter for Lox, with both a tree-walker and a bytecode virtual machine.
+//!
+//! To embed the tree-walker, hand [`run`] a program and the [`Streams`] it should read from
+//! and write to:
+//!
+//! ```
+//! let mut output = Vec::new();
+//! let mut streams = gejang::Streams {
+//!     input: std::io::empty(),
+//!     output: &mut output,
+//!     error: std::io::sink(),
+//! };
+//!
+//! gejang::run("print 1 + 2;", &mut streams, gejang::Options::default()).unwrap();
+//!
+//! assert_eq!(output, b"3\n");
+//! ```
+
+pub mod bytecode;
+mod shared;
+pub mod walker;
+
+pub use shared::streams::Streams;
+pub use walker::{run, InterpreterError, Options};
@@ -1,14 +1,8 @@
-#![feature(test)]
-#![feature(iterator_try_collect)]
-
 use std::path::PathBuf;
 
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
-
-mod bytecode;
-mod shared;
-mod walker;
+use gejang::{bytecode, walker};
 
 #[derive(Parser, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 #[command(version, about, long_about = None)]
@@ -26,12 +20,26 @@ enum Commands {
     #[command(alias = "bc")]
     #[command(alias = "vm")]
     Bytecode(ByteCodeArgs),
+    /// List the language features this build supports
+    Features,
 }
 
 #[derive(Args, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 struct TreeWalkerArgs {
     #[command(subcommand)]
     command: TreeWalkerCommands,
+    /// Instead of executing, print the scope depth each variable reference resolves to.
+    #[arg(long, global = true)]
+    print_resolved_depths: bool,
+    /// Don't report warnings about suspicious code, like an assignment used as a condition.
+    #[arg(long, global = true)]
+    no_warnings: bool,
+    /// Require the bodies of if, else, while, and for statements to be wrapped in braces.
+    #[arg(long, global = true)]
+    strict_braces: bool,
+    /// Report every variable lookup on stderr, along with the scope depth it resolved to.
+    #[arg(long, global = true)]
+    trace: bool,
 }
 
 #[derive(Subcommand, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -40,12 +48,26 @@ enum TreeWalkerCommands {
     Run { script: Option<PathBuf> },
     /// Execute a script passed directly as a string.
     Exec { script: String },
+    /// Execute each script as a test, reporting failed assertions.
+    Test { scripts: Vec<PathBuf> },
+    /// Print the syntax tree of a script (or standard input) without executing it.
+    Ast {
+        script: Option<PathBuf>,
+        /// Print the tree as JSON, for editors and other tools.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the tokens of a script (or standard input) without executing it.
+    Tokens { script: Option<PathBuf> },
 }
 
 #[derive(Args, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 struct ByteCodeArgs {
     #[command(subcommand)]
     command: ByteCodeCommands,
+    /// How many values the virtual machine's stack can hold before it overflows.
+    #[arg(long, global = true, default_value_t = bytecode::DEFAULT_STACK_MAX)]
+    stack_max: usize,
 }
 
 #[derive(Subcommand, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -54,31 +76,82 @@ enum ByteCodeCommands {
     Run { script: Option<PathBuf> },
     /// Execute a script passed directly as a string.
     Exec { script: String },
+    /// Compile a script to bytecode and save it to a file.
+    Compile { script: PathBuf, output: PathBuf },
+    /// Execute bytecode saved by the compile command.
+    RunCompiled { chunk: PathBuf },
+}
+
+/// Read a script from a file, or from standard input if no path is given.
+fn read_source(script: Option<PathBuf>) -> Result<String> {
+    Ok(if let Some(path) = script {
+        std::fs::read_to_string(path)?
+    } else {
+        std::io::read_to_string(std::io::stdin())?
+    })
 }
 
 fn main() -> Result<()> {
-    let args = Cli::parse();
+    let result = run(Cli::parse());
+
+    // A script that calls exit() stops with its own exit code instead of reporting an error.
+    if let Some(walker::InterpreterError::Exit { code }) =
+        result.as_ref().err().and_then(|e| e.downcast_ref())
+    {
+        std::process::exit(*code);
+    }
+
+    result
+}
 
+fn run(args: Cli) -> Result<()> {
     match args.command {
-        Commands::TreeWalker(args) => match args.command {
-            TreeWalkerCommands::Run { script: s } => {
-                if let Some(path) = s {
-                    walker::exec(&std::fs::read_to_string(&path)?)
+        Commands::TreeWalker(args) => {
+            let options = walker::Options {
+                warnings: !args.no_warnings,
+                strict_braces: args.strict_braces,
+                trace: args.trace,
+            };
+            let exec = |source: &str| {
+                if args.print_resolved_depths {
+                    walker::print_resolved_depths(source)
                 } else {
-                    walker::repl()
+                    walker::exec(source, options).map(|_| ())
+                }
+            };
+
+            match args.command {
+                TreeWalkerCommands::Run { script: s } => {
+                    if let Some(path) = s {
+                        exec(&std::fs::read_to_string(&path)?)
+                    } else {
+                        walker::repl(options)
+                    }
+                }
+                TreeWalkerCommands::Exec { script: s } => exec(&s),
+                TreeWalkerCommands::Test { scripts } => walker::test(&scripts, options),
+                TreeWalkerCommands::Ast { script: s, json } => {
+                    walker::print_ast(&read_source(s)?, options, json)
                 }
+                TreeWalkerCommands::Tokens { script: s } => walker::print_tokens(&read_source(s)?),
             }
-            TreeWalkerCommands::Exec { script: s } => walker::exec(&s),
-        },
+        }
         Commands::Bytecode(args) => match args.command {
             ByteCodeCommands::Run { script: s } => {
                 if let Some(path) = s {
-                    bytecode::exec(&std::fs::read_to_string(&path)?)
+                    bytecode::exec(&std::fs::read_to_string(&path)?, args.stack_max)
                 } else {
-                    bytecode::repl()
+                    bytecode::repl(args.stack_max)
                 }
             }
-            ByteCodeCommands::Exec { script: s } => bytecode::exec(&s),
+            ByteCodeCommands::Exec { script: s } => bytecode::exec(&s, args.stack_max),
+            ByteCodeCommands::Compile { script, output } => {
+                bytecode::compile_to_file(&std::fs::read_to_string(&script)?, &output)
+            }
+            ByteCodeCommands::RunCompiled { chunk } => {
+                bytecode::exec_compiled(&std::fs::read(&chunk)?, args.stack_max)
+            }
         },
+        Commands::Features => walker::print_features(),
     }
 }
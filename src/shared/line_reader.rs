@@ -0,0 +1,91 @@
+use std::{
+    cell::RefCell,
+    io,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::shared::streams::Streams;
+
+/// Where an interactive prompt gets its lines from.
+pub trait LineReader {
+    /// Show the prompt and read the next line, without its line ending,
+    /// or `None` if the input has run out.
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>>;
+}
+
+/// Reads lines straight from the input stream, writing prompts to the output stream.
+/// Used when the input isn't a terminal, like when a script is piped in.
+pub struct StreamLineReader<'io, I: Read, O: Write, E: Write> {
+    streams: &'io RefCell<Streams<I, O, E>>,
+}
+
+impl<'io, I: Read, O: Write, E: Write> StreamLineReader<'io, I, O, E> {
+    pub fn new(streams: &'io RefCell<Streams<I, O, E>>) -> Self {
+        StreamLineReader { streams }
+    }
+}
+
+impl<I: Read, O: Write, E: Write> LineReader for StreamLineReader<'_, I, O, E> {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        let mut streams = self.streams.borrow_mut();
+        write!(streams.output, "{prompt}")?;
+        streams.output.flush()?;
+
+        streams.read_line()
+    }
+}
+
+/// Reads lines from a terminal, with line editing and a history of earlier lines.
+/// If it has a history file, the history is loaded from it on creation and saved back on drop,
+/// so it carries over between sessions.
+pub struct EditorLineReader {
+    editor: DefaultEditor,
+    history: Option<PathBuf>,
+}
+
+impl EditorLineReader {
+    pub fn new(history: Option<PathBuf>) -> io::Result<Self> {
+        let mut editor = DefaultEditor::new().map_err(io::Error::other)?;
+        if let Some(path) = &history {
+            // There's no history to load the first time around.
+            let _ = editor.load_history(path);
+        }
+
+        Ok(EditorLineReader { editor, history })
+    }
+}
+
+impl LineReader for EditorLineReader {
+    fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    self.editor
+                        .add_history_entry(line.as_str())
+                        .map_err(io::Error::other)?;
+                }
+                Ok(Some(line))
+            }
+            // Ctrl-C throws away the line being typed, like entering a blank one.
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(ReadlineError::Io(e)) => Err(e),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+impl Drop for EditorLineReader {
+    fn drop(&mut self) {
+        if let Some(path) = &self.history {
+            // Losing the history isn't worth failing over, so errors are ignored.
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = self.editor.save_history(path);
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod line_reader;
+pub mod numbers;
 pub mod scanner;
-pub(crate) mod streams;
+pub mod streams;
@@ -0,0 +1,38 @@
+/// Format a number the way Lox programs see it: whole numbers without a trailing `.0`,
+/// everything else with the fewest digits that round-trip, and scientific notation once the
+/// magnitude gets too large or too small to read comfortably as a plain decimal.
+pub fn format_number(value: f64) -> String {
+    let magnitude = value.abs();
+    if magnitude != 0.0 && value.is_finite() && !(1e-6..1e21).contains(&magnitude) {
+        format!("{value:e}")
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(1.0, "1")]
+    #[case(3.0, "3")]
+    #[case(-2.0, "-2")]
+    #[case(0.0, "0")]
+    #[case(1.5, "1.5")]
+    #[case(10.0 / 4.0, "2.5")]
+    #[case(0.1 + 0.2, "0.30000000000000004")]
+    #[case(1000000.0, "1000000")]
+    #[case(123456789012.0, "123456789012")]
+    #[case(1e21, "1e21")]
+    #[case(0.000001, "0.000001")]
+    #[case(0.0000001, "1e-7")]
+    #[case(-2.5e-10, "-2.5e-10")]
+    #[case(f64::NAN, "NaN")]
+    #[case(f64::INFINITY, "inf")]
+    fn test_format_number(#[case] value: f64, #[case] expected: &str) {
+        assert_eq!(format_number(value), expected);
+    }
+}
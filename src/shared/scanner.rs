@@ -7,19 +7,25 @@ use std::{
 
 use thiserror::Error;
 
-#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
 pub enum TokenType<'s> {
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Amp,
+    Pipe,
+    Caret,
     Bang,
     BangEqual,
     Equal,
@@ -29,21 +35,26 @@ pub enum TokenType<'s> {
     Less,
     LessEqual,
     Identifier(&'s str),
-    String(&'s str),
+    String(Cow<'s, str>),
     Number(f64),
     And,
     Break,
+    Case,
     Class,
+    Continue,
+    Default,
     Else,
     False,
     For,
     Fun,
     If,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
     True,
     Var,
@@ -61,13 +72,19 @@ impl Display for TokenType<'_> {
                 TokenType::RightParen => Cow::from(")"),
                 TokenType::LeftBrace => Cow::from("{"),
                 TokenType::RightBrace => Cow::from("}"),
-                TokenType::Comma => Cow::from("),"),
+                TokenType::LeftBracket => Cow::from("["),
+                TokenType::RightBracket => Cow::from("]"),
+                TokenType::Comma => Cow::from(","),
+                TokenType::Colon => Cow::from(":"),
                 TokenType::Dot => Cow::from("."),
                 TokenType::Minus => Cow::from("-"),
                 TokenType::Plus => Cow::from("+"),
                 TokenType::Semicolon => Cow::from(";"),
                 TokenType::Slash => Cow::from("/"),
                 TokenType::Star => Cow::from("*"),
+                TokenType::Amp => Cow::from("&"),
+                TokenType::Pipe => Cow::from("|"),
+                TokenType::Caret => Cow::from("^"),
                 TokenType::Bang => Cow::from("!"),
                 TokenType::BangEqual => Cow::from("!="),
                 TokenType::Equal => Cow::from("="),
@@ -81,17 +98,22 @@ impl Display for TokenType<'_> {
                 TokenType::Number(n) => Cow::from(format!("number({n})")),
                 TokenType::And => Cow::from("and"),
                 TokenType::Break => Cow::from("break"),
+                TokenType::Case => Cow::from("case"),
                 TokenType::Class => Cow::from("class"),
+                TokenType::Continue => Cow::from("continue"),
+                TokenType::Default => Cow::from("default"),
                 TokenType::Else => Cow::from("else"),
                 TokenType::False => Cow::from("false"),
                 TokenType::For => Cow::from("for"),
                 TokenType::Fun => Cow::from("fun"),
                 TokenType::If => Cow::from("if"),
+                TokenType::In => Cow::from("in"),
                 TokenType::Nil => Cow::from("nil"),
                 TokenType::Or => Cow::from("or"),
                 TokenType::Print => Cow::from("print"),
                 TokenType::Return => Cow::from("return"),
                 TokenType::Super => Cow::from("super"),
+                TokenType::Switch => Cow::from("switch"),
                 TokenType::This => Cow::from("this"),
                 TokenType::True => Cow::from("true"),
                 TokenType::Var => Cow::from("var"),
@@ -118,6 +140,9 @@ pub enum Precedence {
     Or,
     And,
     Equality,
+    BitOr,
+    BitXor,
+    BitAnd,
     Comparison,
     Term,
     Factor,
@@ -133,7 +158,10 @@ impl Precedence {
             Precedence::Assignment => Precedence::Or,
             Precedence::Or => Precedence::And,
             Precedence::And => Precedence::Equality,
-            Precedence::Equality => Precedence::Comparison,
+            Precedence::Equality => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Comparison,
             Precedence::Comparison => Precedence::Term,
             Precedence::Term => Precedence::Factor,
             Precedence::Factor => Precedence::Unary,
@@ -147,6 +175,14 @@ impl Precedence {
 impl TokenType<'_> {
     pub fn precedence(&self) -> Precedence {
         match self {
+            TokenType::EqualEqual | TokenType::BangEqual => Precedence::Equality,
+            TokenType::Pipe => Precedence::BitOr,
+            TokenType::Caret => Precedence::BitXor,
+            TokenType::Amp => Precedence::BitAnd,
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => Precedence::Comparison,
             TokenType::Plus | TokenType::Minus => Precedence::Term,
             TokenType::Star | TokenType::Slash => Precedence::Factor,
             _ => Precedence::None,
@@ -154,11 +190,13 @@ impl TokenType<'_> {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Debug)]
 pub struct Token<'s> {
     pub typ: TokenType<'s>,
     pub lexeme: &'s str,
     pub line: usize,
+    /// Counted in characters from the start of the line, starting at 1.
+    pub column: usize,
 }
 
 #[derive(Error, Clone, PartialEq, PartialOrd, Debug)]
@@ -167,6 +205,10 @@ pub enum ScannerError {
     UnexpectedCharacter { line: usize, char: char },
     #[error("Unterminated string on line {line}")]
     UnterminatedString { line: usize },
+    #[error("Invalid escape sequence on line {line}: \\{char}")]
+    InvalidEscape { line: usize, char: char },
+    #[error("Unterminated block comment starting on line {line}")]
+    UnterminatedComment { line: usize },
     #[error("Invalid number on line {line}: {number}")]
     InvalidNumber { line: usize, number: String },
 }
@@ -180,6 +222,9 @@ struct Scanner<'s> {
     current_offset: usize,
     lexeme_start: usize,
     line: usize,
+    line_start: usize,
+    lexeme_line: usize,
+    lexeme_column: usize,
 }
 
 impl<'s> From<&'s str> for Scanner<'s> {
@@ -189,7 +234,10 @@ impl<'s> From<&'s str> for Scanner<'s> {
             cursor: source.char_indices(),
             current_offset: 0,
             lexeme_start: 0,
-            line: 0,
+            line: 1,
+            line_start: 0,
+            lexeme_line: 1,
+            lexeme_column: 1,
         }
     }
 }
@@ -200,6 +248,7 @@ impl<'s> Scanner<'s> {
             self.current_offset = *offset + c.len_utf8();
             if *c == '\n' {
                 self.line += 1;
+                self.line_start = self.current_offset;
             }
         })
     }
@@ -232,11 +281,13 @@ impl<'s> Scanner<'s> {
         &self.source[self.lexeme_start..self.current_offset]
     }
 
+    /// Tokens are located by where they start, even if they span several lines.
     fn make_token(&self, typ: TokenType<'s>) -> ScannerResult<'s> {
         Ok(Token {
             typ,
             lexeme: self.lexeme(),
-            line: self.line,
+            line: self.lexeme_line,
+            column: self.lexeme_column,
         })
     }
 }
@@ -249,17 +300,25 @@ impl<'s> Iterator for Scanner<'s> {
 
         self.advance().map(|(lexeme_start, c)| {
             self.lexeme_start = lexeme_start;
+            self.lexeme_line = self.line;
+            self.lexeme_column = self.source[self.line_start..lexeme_start].chars().count() + 1;
             match c {
                 '(' => self.make_token(TokenType::LeftParen),
                 ')' => self.make_token(TokenType::RightParen),
                 '{' => self.make_token(TokenType::LeftBrace),
                 '}' => self.make_token(TokenType::RightBrace),
+                '[' => self.make_token(TokenType::LeftBracket),
+                ']' => self.make_token(TokenType::RightBracket),
                 ',' => self.make_token(TokenType::Comma),
+                ':' => self.make_token(TokenType::Colon),
                 '.' => self.make_token(TokenType::Dot),
                 '-' => self.make_token(TokenType::Minus),
                 '+' => self.make_token(TokenType::Plus),
                 ';' => self.make_token(TokenType::Semicolon),
                 '*' => self.make_token(TokenType::Star),
+                '&' => self.make_token(TokenType::Amp),
+                '|' => self.make_token(TokenType::Pipe),
+                '^' => self.make_token(TokenType::Caret),
                 '!' => {
                     if self.advance_if_match('=') {
                         self.make_token(TokenType::BangEqual)
@@ -291,19 +350,69 @@ impl<'s> Iterator for Scanner<'s> {
                 '/' => {
                     if self.advance_if_match('/') {
                         self.advance_while(|c| c != '\n');
+                        self.make_token(TokenType::Comment(self.lexeme()))
+                    } else if self.advance_if_match('*') {
+                        let start_line = self.line;
+                        let mut depth = 1; // block comments nest
+
+                        while depth > 0 {
+                            match self.advance() {
+                                Some((_, '*')) if self.advance_if_match('/') => depth -= 1,
+                                Some((_, '/')) if self.advance_if_match('*') => depth += 1,
+                                Some(_) => {}
+                                None => {
+                                    return Err(ScannerError::UnterminatedComment {
+                                        line: start_line,
+                                    })
+                                }
+                            }
+                        }
+
                         self.make_token(TokenType::Comment(self.lexeme()))
                     } else {
                         self.make_token(TokenType::Slash)
                     }
                 }
                 '"' => {
+                    let mut escaped = false;
+                    let mut invalid_escape = None;
+
                     while let Some((_, c)) = self.advance() {
-                        if c == '"' {
-                            return self.make_token(TokenType::String(
+                        match c {
+                            '"' => {
+                                if let Some(e) = invalid_escape {
+                                    return Err(e);
+                                }
+
                                 // Adjusting the bounds manually here to strip the quotes off is safe,
                                 // because we know that the lexeme is bounded by ASCII quote characters.
-                                self.source[self.lexeme_start + 1..self.current_offset - 1].into(),
-                            ));
+                                let raw =
+                                    &self.source[self.lexeme_start + 1..self.current_offset - 1];
+
+                                return self.make_token(TokenType::String(
+                                    if escaped {
+                                        Cow::from(unescape(raw))
+                                    } else {
+                                        Cow::from(raw)
+                                    },
+                                ));
+                            }
+                            '\\' => {
+                                escaped = true;
+                                // Keep going to the end of the string even if the escape is bad,
+                                // so that the rest of the string isn't scanned as code.
+                                match self.advance() {
+                                    Some((_, 'n' | 't' | 'r' | '\\' | '"')) => {}
+                                    Some((_, c)) => {
+                                        invalid_escape.get_or_insert(ScannerError::InvalidEscape {
+                                            line: self.line,
+                                            char: c,
+                                        });
+                                    }
+                                    None => break,
+                                }
+                            }
+                            _ => {}
                         }
                     }
                     Err(ScannerError::UnterminatedString { line: self.line })
@@ -318,6 +427,14 @@ impl<'s> Iterator for Scanner<'s> {
                         self.advance_while(|c| c.is_ascii_digit());
                     }
 
+                    if self.advance_if_match('e') || self.advance_if_match('E') {
+                        if !self.advance_if_match('+') {
+                            self.advance_if_match('-');
+                        }
+                        // If there are no digits here, parsing below will fail
+                        self.advance_while(|c| c.is_ascii_digit());
+                    }
+
                     if let Ok(number) = self.lexeme().parse() {
                         self.make_token(TokenType::Number(number))
                     } else {
@@ -327,23 +444,28 @@ impl<'s> Iterator for Scanner<'s> {
                         })
                     }
                 }
-                c if c.is_alphabetic() => {
-                    self.advance_while(|c| c.is_alphanumeric() || c == '_');
+                c if c.is_alphabetic() || is_symbol(c) && !c.is_numeric() => {
+                    self.advance_while(|c| c.is_alphanumeric() || c == '_' || is_symbol(c));
 
                     match self.lexeme() {
                         "and" => self.make_token(TokenType::And),
                         "break" => self.make_token(TokenType::Break),
+                        "case" => self.make_token(TokenType::Case),
                         "class" => self.make_token(TokenType::Class),
+                        "continue" => self.make_token(TokenType::Continue),
+                        "default" => self.make_token(TokenType::Default),
                         "else" => self.make_token(TokenType::Else),
                         "false" => self.make_token(TokenType::False),
                         "for" => self.make_token(TokenType::For),
                         "fun" => self.make_token(TokenType::Fun),
                         "if" => self.make_token(TokenType::If),
+                        "in" => self.make_token(TokenType::In),
                         "nil" => self.make_token(TokenType::Nil),
                         "or" => self.make_token(TokenType::Or),
                         "print" => self.make_token(TokenType::Print),
                         "return" => self.make_token(TokenType::Return),
                         "super" => self.make_token(TokenType::Super),
+                        "switch" => self.make_token(TokenType::Switch),
                         "this" => self.make_token(TokenType::This),
                         "true" => self.make_token(TokenType::True),
                         "var" => self.make_token(TokenType::Var),
@@ -360,7 +482,38 @@ impl<'s> Iterator for Scanner<'s> {
     }
 }
 
-pub fn scan(source: &str) -> impl Iterator<Item = ScannerResult> + '_ {
+/// Non-ASCII characters that can appear in identifiers even though they aren't letters, like emoji.
+/// All of the operators are ASCII, so this can't swallow any of them.
+fn is_symbol(c: char) -> bool {
+    !c.is_ascii() && !c.is_whitespace() && !c.is_control()
+}
+
+/// Replace the escape sequences in a string literal (which the scanner has already checked)
+/// with the characters they stand for.
+fn unescape(raw: &str) -> String {
+    let mut s = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        s.push(
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some(c) => c, // \\ and \"
+                    None => unreachable!("String literal ended in the middle of an escape"),
+                }
+            } else {
+                c
+            },
+        );
+    }
+
+    s
+}
+
+pub fn scan(source: &str) -> impl Iterator<Item = ScannerResult<'_>> + '_ {
     Scanner::from(source)
 }
 
@@ -379,221 +532,428 @@ mod tests {
         Ok(Token {
             typ: TokenType::Number(1.0),
             lexeme: "1",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
             typ: TokenType::Plus,
             lexeme: "+",
-            line: 0,
+            line: 1,
+            column: 3,
         }),
         Ok(Token {
             typ: TokenType::Number(2.0),
             lexeme: "2",
-            line: 0,
+            line: 1,
+            column: 5,
+        }),
+    ])]
+    #[case("6&3|4^1", vec![
+        Ok(Token {
+            typ: TokenType::Number(6.0),
+            lexeme: "6",
+            line: 1,
+            column: 1,
+        }),
+        Ok(Token {
+            typ: TokenType::Amp,
+            lexeme: "&",
+            line: 1,
+            column: 2,
+        }),
+        Ok(Token {
+            typ: TokenType::Number(3.0),
+            lexeme: "3",
+            line: 1,
+            column: 3,
+        }),
+        Ok(Token {
+            typ: TokenType::Pipe,
+            lexeme: "|",
+            line: 1,
+            column: 4,
+        }),
+        Ok(Token {
+            typ: TokenType::Number(4.0),
+            lexeme: "4",
+            line: 1,
+            column: 5,
+        }),
+        Ok(Token {
+            typ: TokenType::Caret,
+            lexeme: "^",
+            line: 1,
+            column: 6,
+        }),
+        Ok(Token {
+            typ: TokenType::Number(1.0),
+            lexeme: "1",
+            line: 1,
+            column: 7,
         }),
     ])]
     #[case("\"foo", vec![
-        Err(ScannerError::UnterminatedString { line: 0 }),
+        Err(ScannerError::UnterminatedString { line: 1 }),
     ])]
     #[case("\"foo\"", vec![
         Ok(Token {
-            typ: TokenType::String("foo"),
+            typ: TokenType::String("foo".into()),
             lexeme: "\"foo\"",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
     ])]
     #[case("\"foo\"\n\"bar\"", vec![
         Ok(Token {
-            typ: TokenType::String("foo"),
+            typ: TokenType::String("foo".into()),
             lexeme: "\"foo\"",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
-            typ: TokenType::String("bar"),
+            typ: TokenType::String("bar".into()),
             lexeme: "\"bar\"",
-            line: 1,
+            line: 2,
+            column: 1,
         }),
     ])]
     #[case("123", vec![
         Ok(Token {
             typ: TokenType::Number(123.0),
             lexeme: "123",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
     ])]
     #[case("123.123", vec![
         Ok(Token {
             typ: TokenType::Number(123.123),
             lexeme: "123.123",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
     ])]
     #[case("123.", vec![
         Ok(Token {
             typ: TokenType::Number(123.0),
             lexeme: "123",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
             typ: TokenType::Dot,
             lexeme: ".",
-            line: 0,
+            line: 1,
+            column: 4,
+        }),
+    ])]
+    #[case("6.022e23", vec![
+        Ok(Token {
+            typ: TokenType::Number(6.022e23),
+            lexeme: "6.022e23",
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("1.5e-3", vec![
+        Ok(Token {
+            typ: TokenType::Number(1.5e-3),
+            lexeme: "1.5e-3",
+            line: 1,
+            column: 1,
         }),
     ])]
+    #[case("2E+2", vec![
+        Ok(Token {
+            typ: TokenType::Number(200.0),
+            lexeme: "2E+2",
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("1e", vec![
+        Err(ScannerError::InvalidNumber { line: 1, number: "1e".into() }),
+    ])]
+    #[case("1e-", vec![
+        Err(ScannerError::InvalidNumber { line: 1, number: "1e-".into() }),
+    ])]
     #[case("123.foo", vec![
         Ok(Token {
             typ: TokenType::Number(123.0),
             lexeme: "123",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
             typ: TokenType::Dot,
             lexeme: ".",
-            line: 0,
+            line: 1,
+            column: 4,
         }),
         Ok(Token {
             typ: TokenType::Identifier("foo"),
             lexeme: "foo",
-            line: 0,
+            line: 1,
+            column: 5,
         }),
     ])]
     #[case("printfoo", vec![
         Ok(Token {
             typ: TokenType::Identifier("printfoo"),
             lexeme: "printfoo",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
     ])]
     #[case("print foo", vec![
         Ok(Token {
             typ: TokenType::Print,
             lexeme: "print",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
             typ: TokenType::Identifier("foo"),
             lexeme: "foo",
-            line: 0,
+            line: 1,
+            column: 7,
         }),
     ])]
     #[case("안녕하세요", vec![
         Ok(Token {
             typ: TokenType::Identifier("안녕하세요"),
             lexeme: "안녕하세요",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
     ])]
     #[case("λ", vec![
         Ok(Token {
             typ: TokenType::Identifier("λ"),
             lexeme: "λ",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
     ])]
     #[case("λ + bar", vec![
         Ok(Token {
             typ: TokenType::Identifier("λ"),
             lexeme: "λ",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
             typ: TokenType::Plus,
             lexeme: "+",
-            line: 0,
+            line: 1,
+            column: 3,
         }),
         Ok(Token {
             typ: TokenType::Identifier("bar"),
             lexeme: "bar",
-            line: 0,
+            line: 1,
+            column: 5,
         }),
     ])]
     #[case("λ.bar", vec![
         Ok(Token {
             typ: TokenType::Identifier("λ"),
             lexeme: "λ",
-            line: 0,
+            line: 1,
+            column: 1,
         }),
         Ok(Token {
             typ: TokenType::Dot,
             lexeme: ".",
-            line: 0,
+            line: 1,
+            column: 2,
         }),
         Ok(Token {
             typ: TokenType::Identifier("bar"),
             lexeme: "bar",
-            line: 0,
+            line: 1,
+            column: 3,
         }),
     ])]
     #[case("\"λ\"", vec![
         Ok(Token {
-            typ: TokenType::String("λ"),
+            typ: TokenType::String("λ".into()),
             lexeme: "\"λ\"",
-            line: 0,
-        }),
-    ])]
-    // TODO: support emoji identifiers
-    // #[case("🦀", vec![
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("🦀"),
-    //         lexeme: "🦀",
-    //         line: 0,
-    //     }),
-    // ])]
-    // #[case("🦀 + bar", vec![
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("🦀"),
-    //         lexeme: "🦀",
-    //         line: 0,
-    //     }),
-    //     Ok(Token {
-    //         typ: TokenType::Plus,
-    //         lexeme: "+",
-    //         line: 0,
-    //     }),
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("bar"),
-    //         lexeme: "bar",
-    //         line: 0,
-    //     }),
-    // ])]
-    // #[case("🦀.bar", vec![
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("🦀"),
-    //         lexeme: "🦀",
-    //         line: 0,
-    //     }),
-    //     Ok(Token {
-    //         typ: TokenType::Dot,
-    //         lexeme: ".",
-    //         line: 0,
-    //     }),
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("bar"),
-    //         lexeme: "bar",
-    //         line: 0,
-    //     }),
-    // ])]
-    // #[case("🦀.λ", vec![
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("🦀"),
-    //         lexeme: "🦀",
-    //         line: 0,
-    //     }),
-    //     Ok(Token {
-    //         typ: TokenType::Dot,
-    //         lexeme: ".",
-    //         line: 0,
-    //     }),
-    //     Ok(Token {
-    //         typ: TokenType::Identifier("λ"),
-    //         lexeme: "λ",
-    //         line: 0,
-    //     }),
-    // ])]
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("/* one */", vec![
+        Ok(Token {
+            typ: TokenType::Comment("/* one */"),
+            lexeme: "/* one */",
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("1 /* a\nb */ 2", vec![
+        Ok(Token {
+            typ: TokenType::Number(1.0),
+            lexeme: "1",
+            line: 1,
+            column: 1,
+        }),
+        Ok(Token {
+            typ: TokenType::Comment("/* a\nb */"),
+            lexeme: "/* a\nb */",
+            line: 1,
+            column: 3,
+        }),
+        Ok(Token {
+            typ: TokenType::Number(2.0),
+            lexeme: "2",
+            line: 2,
+            column: 6,
+        }),
+    ])]
+    #[case("/* outer /* inner */ still outer */", vec![
+        Ok(Token {
+            typ: TokenType::Comment("/* outer /* inner */ still outer */"),
+            lexeme: "/* outer /* inner */ still outer */",
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("/**/ /", vec![
+        Ok(Token {
+            typ: TokenType::Comment("/**/"),
+            lexeme: "/**/",
+            line: 1,
+            column: 1,
+        }),
+        Ok(Token {
+            typ: TokenType::Slash,
+            lexeme: "/",
+            line: 1,
+            column: 6,
+        }),
+    ])]
+    #[case("\n/* never\n ends", vec![
+        Err(ScannerError::UnterminatedComment { line: 2 }),
+    ])]
+    #[case("/* outer /* inner */", vec![
+        Err(ScannerError::UnterminatedComment { line: 1 }),
+    ])]
+    #[case("🦀", vec![
+        Ok(Token {
+            typ: TokenType::Identifier("🦀"),
+            lexeme: "🦀",
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("🦀 + bar", vec![
+        Ok(Token {
+            typ: TokenType::Identifier("🦀"),
+            lexeme: "🦀",
+            line: 1,
+            column: 1,
+        }),
+        Ok(Token {
+            typ: TokenType::Plus,
+            lexeme: "+",
+            line: 1,
+            column: 3,
+        }),
+        Ok(Token {
+            typ: TokenType::Identifier("bar"),
+            lexeme: "bar",
+            line: 1,
+            column: 5,
+        }),
+    ])]
+    #[case("🦀.bar", vec![
+        Ok(Token {
+            typ: TokenType::Identifier("🦀"),
+            lexeme: "🦀",
+            line: 1,
+            column: 1,
+        }),
+        Ok(Token {
+            typ: TokenType::Dot,
+            lexeme: ".",
+            line: 1,
+            column: 2,
+        }),
+        Ok(Token {
+            typ: TokenType::Identifier("bar"),
+            lexeme: "bar",
+            line: 1,
+            column: 3,
+        }),
+    ])]
+    #[case("🦀.λ", vec![
+        Ok(Token {
+            typ: TokenType::Identifier("🦀"),
+            lexeme: "🦀",
+            line: 1,
+            column: 1,
+        }),
+        Ok(Token {
+            typ: TokenType::Dot,
+            lexeme: ".",
+            line: 1,
+            column: 2,
+        }),
+        Ok(Token {
+            typ: TokenType::Identifier("λ"),
+            lexeme: "λ",
+            line: 1,
+            column: 3,
+        }),
+    ])]
+    #[case("crab_🦀2", vec![
+        Ok(Token {
+            typ: TokenType::Identifier("crab_🦀2"),
+            lexeme: "crab_🦀2",
+            line: 1,
+            column: 1,
+        }),
+    ])]
+    #[case("٣", vec![
+        Err(ScannerError::UnexpectedCharacter { line: 1, char: '٣' }),
+    ])]
     fn test_scanner(#[case] source: &str, #[case] expected: Vec<Result<Token, ScannerError>>) {
         assert_eq!(scan(source).collect_vec(), expected);
     }
 
+    #[rstest]
+    #[case(r#""a\nb""#, Ok("a\nb"))]
+    #[case(r#""a\tb""#, Ok("a\tb"))]
+    #[case(r#""a\rb""#, Ok("a\rb"))]
+    #[case(r#""a\\b""#, Ok("a\\b"))]
+    #[case(r#""say \"hi\"""#, Ok("say \"hi\""))]
+    #[case(r#""\\n""#, Ok("\\n"))]
+    #[case(r#""🦀\t🦀""#, Ok("🦀\t🦀"))]
+    #[case(r#""a\qb""#, Err(ScannerError::InvalidEscape { line: 1, char: 'q' }))]
+    #[case("\"a\nb\\q\"", Err(ScannerError::InvalidEscape { line: 2, char: 'q' }))]
+    #[case(r#""a\""#, Err(ScannerError::UnterminatedString { line: 1 }))]
+    fn test_string_escapes(#[case] source: &str, #[case] expected: Result<&str, ScannerError>) {
+        let tokens = scan(source).collect_vec();
+
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            tokens[0].clone().map(|t| match t.typ {
+                TokenType::String(s) => s.into_owned(),
+                typ => panic!("Expected a string, got {typ}"),
+            }),
+            expected.map(String::from)
+        );
+    }
+
+    #[test]
+    fn test_unescaped_strings_are_borrowed() {
+        let tokens: Vec<Token> = scan(r#""plain" "esc\n""#).try_collect().unwrap();
+
+        assert!(matches!(tokens[0].typ, TokenType::String(Cow::Borrowed(_))));
+        assert!(matches!(tokens[1].typ, TokenType::String(Cow::Owned(_))));
+    }
+
     #[test]
     fn scan_hello_world() {
         let source = include_str!(concat!(
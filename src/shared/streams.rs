@@ -10,6 +10,31 @@ pub struct Streams<I: Read, O: Write, E: Write> {
     pub error: E,
 }
 
+impl<I: Read, O: Write, E: Write> Streams<I, O, E> {
+    /// Read the next line of input, without its line ending, or `None` if the input has run out.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        // Read a byte at a time so we never consume input past the end of this line,
+        // since the input stream is shared with whatever else is reading it (like the REPL).
+        let mut bytes = Vec::new();
+        let mut byte = [0u8];
+        loop {
+            match self.input.read(&mut byte)? {
+                0 if bytes.is_empty() => return Ok(None),
+                0 => break,
+                _ if byte[0] == b'\n' => break,
+                _ => bytes.push(byte[0]),
+            }
+        }
+        if bytes.last() == Some(&b'\r') {
+            bytes.pop();
+        }
+
+        String::from_utf8(bytes)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
 impl Streams<Stdin, Stdout, Stderr> {
     pub fn new() -> Self {
         Streams {
@@ -20,6 +45,12 @@ impl Streams<Stdin, Stdout, Stderr> {
     }
 }
 
+impl Default for Streams<Stdin, Stdout, Stderr> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 impl Streams<&[u8], Vec<u8>, Vec<u8>> {
     pub fn test() -> Self {
@@ -21,13 +21,18 @@ pub enum Expr<'s> {
     },
     Call {
         callee: BoxedExpr<'s>,
-        // paren: RefToken<'s>,
+        paren: RefToken<'s>,
         args: Vec<Expr<'s>>,
     },
     Get {
         object: BoxedExpr<'s>,
         name: RefToken<'s>,
     },
+    Index {
+        object: BoxedExpr<'s>,
+        bracket: RefToken<'s>,
+        index: BoxedExpr<'s>,
+    },
     Unary {
         op: RefToken<'s>,
         right: BoxedExpr<'s>,
@@ -35,19 +40,41 @@ pub enum Expr<'s> {
     Grouping {
         expr: BoxedExpr<'s>,
     },
+    List {
+        items: Vec<Expr<'s>>,
+    },
     Literal {
         value: RefToken<'s>,
     },
+    Map {
+        brace: RefToken<'s>,
+        entries: Vec<(Expr<'s>, Expr<'s>)>,
+    },
     Logical {
         left: BoxedExpr<'s>,
         op: RefToken<'s>,
         right: BoxedExpr<'s>,
     },
+    Slice {
+        object: BoxedExpr<'s>,
+        start: Option<BoxedExpr<'s>>,
+        end: Option<BoxedExpr<'s>>,
+    },
     Set {
         object: BoxedExpr<'s>,
         name: RefToken<'s>,
         value: BoxedExpr<'s>,
     },
+    SetIndex {
+        object: BoxedExpr<'s>,
+        bracket: RefToken<'s>,
+        index: BoxedExpr<'s>,
+        value: BoxedExpr<'s>,
+    },
+    Super {
+        keyword: RefToken<'s>,
+        method: RefToken<'s>,
+    },
     This {
         keyword: RefToken<'s>,
     },
@@ -68,11 +95,8 @@ impl Display for Expr<'_> {
                 Expr::Binary { left, op, right } => {
                     format!("({} {} {})", op.lexeme, left, right)
                 }
-                Expr::Call {
-                    callee,
-                    // paren,
-                    args,
-                } => {
+                Expr::Call { callee, args, .. } if args.is_empty() => format!("({})", callee),
+                Expr::Call { callee, args, .. } => {
                     format!(
                         "({} {})",
                         callee,
@@ -82,6 +106,18 @@ impl Display for Expr<'_> {
                 Expr::Get { object, name } => {
                     format!("(get {} {})", object, name.lexeme)
                 }
+                Expr::Index { object, index, .. } => {
+                    format!("(index {} {})", object, index)
+                }
+                Expr::Map { entries, .. } => {
+                    format!(
+                        "(map {})",
+                        entries.iter().map(|(k, v)| format!("{k}: {v}")).join(", ")
+                    )
+                }
+                Expr::List { items } => {
+                    format!("(list {})", items.iter().map(|i| i.to_string()).join(", "))
+                }
                 Expr::Unary { op, right } => {
                     format!("({} {})", op.lexeme, right)
                 }
@@ -90,9 +126,17 @@ impl Display for Expr<'_> {
                 }
                 Expr::Literal { value: token } => token.lexeme.into(),
                 Expr::Logical { left, op, right } => {
-                    format!("({} {} {}", op.lexeme, left, right)
+                    format!("({} {} {})", op.lexeme, left, right)
                 }
                 Expr::Variable { name } => name.lexeme.into(),
+                Expr::Slice { object, start, end } => {
+                    format!(
+                        "(slice {} {} {})",
+                        object,
+                        start.as_ref().map_or("_".to_string(), |s| s.to_string()),
+                        end.as_ref().map_or("_".to_string(), |e| e.to_string())
+                    )
+                }
                 Expr::Set {
                     object,
                     name,
@@ -100,6 +144,17 @@ impl Display for Expr<'_> {
                 } => {
                     format!("(set {} {} {})", object, name.lexeme, value)
                 }
+                Expr::SetIndex {
+                    object,
+                    index,
+                    value,
+                    ..
+                } => {
+                    format!("(set-index {} {} {})", object, index, value)
+                }
+                Expr::Super { method, .. } => {
+                    format!("super.{}", method.lexeme)
+                }
                 Expr::This { .. } => {
                     "this".to_string()
                 }
@@ -113,10 +168,17 @@ pub enum Stmt<'s> {
     Block {
         stmts: Vec<Stmt<'s>>,
     },
-    Break,
+    Break {
+        keyword: RefToken<'s>,
+    },
+    Continue {
+        keyword: RefToken<'s>,
+    },
     Class {
         name: RefToken<'s>,
+        superclass: Option<BoxedExpr<'s>>,
         methods: Vec<Stmt<'s>>,
+        doc: Option<String>,
     },
     Expression {
         expr: BoxedExpr<'s>,
@@ -125,6 +187,9 @@ pub enum Stmt<'s> {
         name: RefToken<'s>,
         params: Vec<RefToken<'s>>,
         body: Vec<Stmt<'s>>,
+        doc: Option<String>,
+        /// A method declared without a parameter list, which runs when it is accessed.
+        getter: bool,
     },
     If {
         condition: BoxedExpr<'s>,
@@ -132,18 +197,33 @@ pub enum Stmt<'s> {
         els: Option<BoxedStmt<'s>>,
     },
     Print {
-        expr: BoxedExpr<'s>,
+        exprs: Vec<Expr<'s>>,
     },
     Return {
+        keyword: RefToken<'s>,
         value: Option<BoxedExpr<'s>>,
     },
+    Switch {
+        subject: BoxedExpr<'s>,
+        /// Only the body of the first case whose value equals the subject runs;
+        /// there is no fallthrough.
+        cases: Vec<(Expr<'s>, Vec<Stmt<'s>>)>,
+        default: Option<Vec<Stmt<'s>>>,
+    },
     Var {
         name: RefToken<'s>,
         initializer: Option<BoxedExpr<'s>>,
     },
+    ForIn {
+        name: RefToken<'s>,
+        iterable: BoxedExpr<'s>,
+        body: BoxedStmt<'s>,
+    },
     While {
         condition: BoxedExpr<'s>,
         body: BoxedStmt<'s>,
+        /// The increment of a desugared `for` loop, which runs after the body even on `continue`.
+        increment: Option<BoxedExpr<'s>>,
     },
 }
 
@@ -154,33 +234,41 @@ impl Display for Stmt<'_> {
             "{}",
             match self {
                 Stmt::Block { stmts } => {
-                    format!("(block {}", stmts.iter().map(|s| s.to_string()).join(" "))
+                    format!("(block {})", stmts.iter().map(|s| s.to_string()).join(" "))
                 }
                 Stmt::Expression { expr } => {
                     format!("(expression {expr})")
                 }
-                Stmt::Function { name, params, body } => {
+                Stmt::Function {
+                    name, params, body, ..
+                } => {
                     format!(
-                        "(function {} ({}) ({})",
+                        "(function {} ({}) ({}))",
                         name.lexeme,
                         params.iter().map(|p| p.lexeme).join(" "),
                         body.iter().map(|s| s.to_string()).join(" ")
                     )
                 }
-                Stmt::Class { name, methods } => {
+                Stmt::Class {
+                    name,
+                    superclass,
+                    methods,
+                    ..
+                } => {
                     format!(
-                        "(class {} {})",
+                        "(class {}{} {})",
                         name.lexeme,
+                        superclass
+                            .as_ref()
+                            .map(|s| format!(" < {s}"))
+                            .unwrap_or_default(),
                         methods
                             .iter()
-                            .map(|m| format!(
-                                "({})",
-                                if let Stmt::Function { name, .. } = m {
-                                    name.lexeme
-                                } else {
-                                    ""
-                                }
-                            ))
+                            .map(|m| if let Stmt::Function { name, params, .. } = m {
+                                format!("({0} {0}/{1})", name.lexeme, params.len())
+                            } else {
+                                m.to_string()
+                            })
                             .join(" "),
                     )
                 }
@@ -190,15 +278,19 @@ impl Display for Stmt<'_> {
                     els,
                 } => {
                     if let Some(e) = els {
-                        format!("(if {} then {} else {}", condition, then, e)
+                        format!("(if {} then {} else {})", condition, then, e)
                     } else {
-                        format!("(if {} then {}", condition, then)
+                        format!("(if {} then {})", condition, then)
                     }
                 }
-                Stmt::Print { expr } => {
-                    format!("(print {expr})")
+                Stmt::Print { exprs } => {
+                    if exprs.is_empty() {
+                        "(print)".to_string()
+                    } else {
+                        format!("(print {})", exprs.iter().join(" "))
+                    }
                 }
-                Stmt::Return { value } => {
+                Stmt::Return { value, .. } => {
                     match value {
                         Some(v) => format!("(return {v})"),
                         None => "(return)".to_string(),
@@ -211,10 +303,42 @@ impl Display for Stmt<'_> {
                         format!("(var {})", name.lexeme)
                     }
                 }
-                Stmt::While { condition, body } => {
-                    format!("(while {} {})", condition, body)
+                Stmt::ForIn {
+                    name,
+                    iterable,
+                    body,
+                } => format!("(for {} {} {})", name.lexeme, iterable, body),
+                Stmt::While {
+                    condition,
+                    body,
+                    increment,
+                } => {
+                    if let Some(i) = increment {
+                        format!("(while {} {} {})", condition, body, i)
+                    } else {
+                        format!("(while {} {})", condition, body)
+                    }
+                }
+                Stmt::Switch {
+                    subject,
+                    cases,
+                    default,
+                } => {
+                    let body = |stmts: &Vec<Stmt>| stmts.iter().map(|s| s.to_string()).join(" ");
+                    format!(
+                        "(switch {} {}{})",
+                        subject,
+                        cases
+                            .iter()
+                            .map(|(value, stmts)| format!("(case {} {})", value, body(stmts)))
+                            .join(" "),
+                        default
+                            .as_ref()
+                            .map_or(String::new(), |d| format!(" (default {})", body(d)))
+                    )
                 }
-                Stmt::Break => "(break)".into(),
+                Stmt::Break { .. } => "(break)".into(),
+                Stmt::Continue { .. } => "(continue)".into(),
             }
         )
     }
@@ -222,11 +346,12 @@ impl Display for Stmt<'_> {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
     use rstest::rstest;
 
     use crate::{
-        shared::scanner::{Token, TokenType},
-        walker::ast::*,
+        shared::scanner::{scan, Token, TokenType},
+        walker::{ast::*, parser::parse},
     };
 
     #[rstest]
@@ -236,21 +361,22 @@ mod tests {
                 value: &Token {
                     typ: TokenType::Number(1.0),
                     lexeme: "1",
-                    line: 0,
+                    line: 1,
+                    column: 1,
                 },
             }),
             op: &Token {
                 typ: TokenType::Plus,
                 lexeme: "+",
-                line: 0,
-
+                line: 1,
+                column: 3,
             },
             right: Box::new(Expr::Literal {
                 value: &Token {
                     typ: TokenType::Number(2.0),
                     lexeme: "2",
-                    line: 0,
-
+                    line: 1,
+                    column: 5,
                 },
             }),
             },
@@ -262,31 +388,31 @@ mod tests {
                 op: &Token {
                     typ: TokenType::Minus,
                     lexeme: "-",
-                    line: 0,
-
+                    line: 1,
+                    column: 1,
                 },
                 right: Box::new(Expr::Literal {
                     value: &Token {
                         typ: TokenType::Number(1.0),
                         lexeme: "1",
-                        line: 0,
-
+                        line: 1,
+                        column: 2,
                     },
                 }),
             }),
             op: &Token {
                 typ: TokenType::Star,
                 lexeme: "*",
-                line: 0,
-
+                line: 1,
+                column: 4,
             },
             right: Box::new(Expr::Grouping {
                 expr: Box::new(Expr::Literal {
                     value: &Token {
                         typ: TokenType::Number(2.0),
                         lexeme: "2",
-                        line: 0,
-
+                        line: 1,
+                        column: 7,
                     },
                 }),
             }),
@@ -296,4 +422,30 @@ mod tests {
     fn test_printer(#[case] input: Expr, #[case] expected: &str) {
         assert_eq!(input.to_string(), expected);
     }
+
+    #[rstest]
+    #[case("{ print 1; print 2; }", "(block (print 1) (print 2))")]
+    #[case("{}", "(block )")]
+    #[case("fun f(a, b) { return a; }", "(function f (a b) ((return a)))")]
+    #[case("if (x) print 1;", "(if x then (print 1))")]
+    #[case(
+        "if (x) { print 1; } else print 2;",
+        "(if x then (block (print 1)) else (print 2))"
+    )]
+    #[case("print a and b or c;", "(print (or (and a b) c))")]
+    #[case(
+        "class Foo { method(x) {} other() {} }",
+        "(class Foo (method method/1) (other other/0))"
+    )]
+    #[case("class Bar < Foo { size {} }", "(class Bar < Foo (size size/0))")]
+    #[case(
+        "for (var i = 0; i < 2; i = i + 1) { print i; }",
+        "(block (var i 0) (while (< i 2) (block (print i)) (assign i (+ i 1))))"
+    )]
+    fn test_stmt_printer(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmt = parse(tokens.iter()).into_iter().next().unwrap().unwrap();
+
+        assert_eq!(stmt.to_string(), expected);
+    }
 }
@@ -0,0 +1,332 @@
+use itertools::Itertools;
+
+use crate::{
+    shared::scanner::Token,
+    walker::ast::{Expr, Stmt},
+};
+
+/// Describe the syntax tree as JSON, for tools that want to work with it.
+///
+/// Every node is an object whose `"type"` is the name of its `Stmt` or `Expr` variant,
+/// followed by its fields in the order they're declared in the AST.
+/// Tokens are objects with their `"lexeme"`, `"line"`, and `"column"`,
+/// and missing optional children are `null`.
+pub fn to_json(stmts: &[Stmt]) -> String {
+    array(stmts.iter().map(stmt))
+}
+
+fn stmt(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Block { stmts } => node("Block", [("stmts", statements(stmts))]),
+        Stmt::Break { keyword } => node("Break", [("keyword", token(keyword))]),
+        Stmt::Continue { keyword } => node("Continue", [("keyword", token(keyword))]),
+        Stmt::Class {
+            name,
+            superclass,
+            methods,
+            doc,
+        } => node(
+            "Class",
+            [
+                ("name", token(name)),
+                ("superclass", optional(superclass.as_deref().map(expr))),
+                ("methods", statements(methods)),
+                ("doc", optional(doc.as_deref().map(string))),
+            ],
+        ),
+        Stmt::Expression { expr: e } => node("Expression", [("expr", expr(e))]),
+        Stmt::Function {
+            name,
+            params,
+            body,
+            doc,
+            getter,
+        } => node(
+            "Function",
+            [
+                ("name", token(name)),
+                ("params", array(params.iter().map(|p| token(p)))),
+                ("body", statements(body)),
+                ("doc", optional(doc.as_deref().map(string))),
+                ("getter", getter.to_string()),
+            ],
+        ),
+        Stmt::If {
+            condition,
+            then,
+            els,
+        } => node(
+            "If",
+            [
+                ("condition", expr(condition)),
+                ("then", self::stmt(then)),
+                ("els", optional(els.as_deref().map(self::stmt))),
+            ],
+        ),
+        Stmt::Print { exprs } => node("Print", [("exprs", array(exprs.iter().map(expr)))]),
+        Stmt::Return { keyword, value } => node(
+            "Return",
+            [
+                ("keyword", token(keyword)),
+                ("value", optional(value.as_deref().map(expr))),
+            ],
+        ),
+        Stmt::Switch {
+            subject,
+            cases,
+            default,
+        } => node(
+            "Switch",
+            [
+                ("subject", expr(subject)),
+                (
+                    "cases",
+                    array(cases.iter().map(|(value, body)| {
+                        object([("value", expr(value)), ("body", statements(body))])
+                    })),
+                ),
+                ("default", optional(default.as_deref().map(statements))),
+            ],
+        ),
+        Stmt::Var { name, initializer } => node(
+            "Var",
+            [
+                ("name", token(name)),
+                ("initializer", optional(initializer.as_deref().map(expr))),
+            ],
+        ),
+        Stmt::ForIn {
+            name,
+            iterable,
+            body,
+        } => node(
+            "ForIn",
+            [
+                ("name", token(name)),
+                ("iterable", expr(iterable)),
+                ("body", self::stmt(body)),
+            ],
+        ),
+        Stmt::While {
+            condition,
+            body,
+            increment,
+        } => node(
+            "While",
+            [
+                ("condition", expr(condition)),
+                ("body", self::stmt(body)),
+                ("increment", optional(increment.as_deref().map(expr))),
+            ],
+        ),
+    }
+}
+
+fn expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign { name, value } => node(
+            "Assign",
+            [("name", token(name)), ("value", self::expr(value))],
+        ),
+        Expr::Binary { left, op, right } => node(
+            "Binary",
+            [
+                ("left", self::expr(left)),
+                ("op", token(op)),
+                ("right", self::expr(right)),
+            ],
+        ),
+        Expr::Call {
+            callee,
+            paren,
+            args,
+        } => node(
+            "Call",
+            [
+                ("callee", self::expr(callee)),
+                ("paren", token(paren)),
+                ("args", array(args.iter().map(self::expr))),
+            ],
+        ),
+        Expr::Get { object, name } => node(
+            "Get",
+            [("object", self::expr(object)), ("name", token(name))],
+        ),
+        Expr::Index {
+            object,
+            bracket,
+            index,
+        } => node(
+            "Index",
+            [
+                ("object", self::expr(object)),
+                ("bracket", token(bracket)),
+                ("index", self::expr(index)),
+            ],
+        ),
+        Expr::Unary { op, right } => {
+            node("Unary", [("op", token(op)), ("right", self::expr(right))])
+        }
+        Expr::Grouping { expr } => node("Grouping", [("expr", self::expr(expr))]),
+        Expr::List { items } => node("List", [("items", array(items.iter().map(self::expr)))]),
+        Expr::Literal { value } => node("Literal", [("value", token(value))]),
+        Expr::Map { brace, entries } => node(
+            "Map",
+            [
+                ("brace", token(brace)),
+                (
+                    "entries",
+                    array(entries.iter().map(|(key, value)| {
+                        object([("key", self::expr(key)), ("value", self::expr(value))])
+                    })),
+                ),
+            ],
+        ),
+        Expr::Logical { left, op, right } => node(
+            "Logical",
+            [
+                ("left", self::expr(left)),
+                ("op", token(op)),
+                ("right", self::expr(right)),
+            ],
+        ),
+        Expr::Slice { object, start, end } => node(
+            "Slice",
+            [
+                ("object", self::expr(object)),
+                ("start", optional(start.as_deref().map(self::expr))),
+                ("end", optional(end.as_deref().map(self::expr))),
+            ],
+        ),
+        Expr::Set {
+            object,
+            name,
+            value,
+        } => node(
+            "Set",
+            [
+                ("object", self::expr(object)),
+                ("name", token(name)),
+                ("value", self::expr(value)),
+            ],
+        ),
+        Expr::SetIndex {
+            object,
+            bracket,
+            index,
+            value,
+        } => node(
+            "SetIndex",
+            [
+                ("object", self::expr(object)),
+                ("bracket", token(bracket)),
+                ("index", self::expr(index)),
+                ("value", self::expr(value)),
+            ],
+        ),
+        Expr::Super { keyword, method } => node(
+            "Super",
+            [("keyword", token(keyword)), ("method", token(method))],
+        ),
+        Expr::This { keyword } => node("This", [("keyword", token(keyword))]),
+        Expr::Variable { name } => node("Variable", [("name", token(name))]),
+    }
+}
+
+fn statements(stmts: &[Stmt]) -> String {
+    array(stmts.iter().map(stmt))
+}
+
+fn token(token: &Token) -> String {
+    object([
+        ("lexeme", string(token.lexeme)),
+        ("line", token.line.to_string()),
+        ("column", token.column.to_string()),
+    ])
+}
+
+fn node<const N: usize>(typ: &str, fields: [(&str, String); N]) -> String {
+    object(std::iter::once(("type", string(typ))).chain(fields))
+}
+
+fn object<'a>(fields: impl IntoIterator<Item = (&'a str, String)>) -> String {
+    format!(
+        "{{{}}}",
+        fields
+            .into_iter()
+            .map(|(key, value)| format!("{}:{}", string(key), value))
+            .join(",")
+    )
+}
+
+fn array(mut items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.join(","))
+}
+
+fn optional(value: Option<String>) -> String {
+    value.unwrap_or_else(|| "null".to_string())
+}
+
+fn string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c < ' ' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{shared::scanner::scan, walker::parser::parse};
+
+    #[rstest]
+    #[case(
+        "1 + 2;",
+        r#"[{"type":"Expression","expr":{"type":"Binary","left":{"type":"Literal","value":{"lexeme":"1","line":1,"column":1}},"op":{"lexeme":"+","line":1,"column":3},"right":{"type":"Literal","value":{"lexeme":"2","line":1,"column":5}}}}]"#
+    )]
+    #[case(
+        "var x;\nreturn;",
+        r#"[{"type":"Var","name":{"lexeme":"x","line":1,"column":5},"initializer":null},{"type":"Return","keyword":{"lexeme":"return","line":2,"column":1},"value":null}]"#
+    )]
+    #[case(
+        "print \"say \\\"hi\\\"\";",
+        r#"[{"type":"Print","exprs":[{"type":"Literal","value":{"lexeme":"\"say \\\"hi\\\"\"","line":1,"column":7}}]}]"#
+    )]
+    #[case(
+        "fun f(a) { a; }",
+        r#"[{"type":"Function","name":{"lexeme":"f","line":1,"column":5},"params":[{"lexeme":"a","line":1,"column":7}],"body":[{"type":"Expression","expr":{"type":"Variable","name":{"lexeme":"a","line":1,"column":12}}}],"doc":null,"getter":false}]"#
+    )]
+    #[case("", "[]")]
+    fn test_to_json(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<_> = scan(source).collect::<Result<_, _>>().unwrap();
+        let stmts: Vec<_> = parse(tokens.iter())
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(to_json(&stmts), expected);
+    }
+
+    #[rstest]
+    #[case("plain", r#""plain""#)]
+    #[case("a\"b\\c", r#""a\"b\\c""#)]
+    #[case("line\nbreak\ttab", r#""line\nbreak\ttab""#)]
+    #[case("\u{1}", r#""\u0001""#)]
+    #[case("🦀", r#""🦀""#)]
+    fn test_string_escapes(#[case] s: &str, #[case] expected: &str) {
+        assert_eq!(string(s), expected);
+    }
+}
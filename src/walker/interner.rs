@@ -0,0 +1,100 @@
+use std::{collections::HashSet, rc::Rc};
+
+/// How many strings the pool holds before it first sweeps out ones nothing else refers to.
+const INITIAL_SWEEP_AT: usize = 1024;
+
+/// A pool of shared strings, so that equal strings built while a program runs
+/// share one allocation and can usually be compared by pointer.
+#[derive(Debug)]
+pub struct Interner {
+    strings: HashSet<Rc<str>>,
+    /// Reused to build strings, so looking up one that is already pooled doesn't allocate.
+    scratch: String,
+    sweep_at: usize,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self {
+            strings: HashSet::new(),
+            scratch: String::new(),
+            sweep_at: INITIAL_SWEEP_AT,
+        }
+    }
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(existing) = self.strings.get(s) {
+            return existing.clone();
+        }
+
+        // Strings that only the pool still holds will never be handed out again unless rebuilt,
+        // so sweep them out before the pool grows, instead of keeping every string ever made.
+        if self.strings.len() >= self.sweep_at {
+            self.strings.retain(|s| Rc::strong_count(s) > 1);
+            self.sweep_at = (self.strings.len() * 2).max(INITIAL_SWEEP_AT);
+        }
+
+        let interned: Rc<str> = Rc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Intern the concatenation of two strings.
+    pub fn concat(&mut self, left: &str, right: &str) -> Rc<str> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        scratch.clear();
+        scratch.push_str(left);
+        scratch.push_str(right);
+
+        let interned = self.intern(&scratch);
+        self.scratch = scratch;
+        interned
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.strings.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_equal_strings_share_storage() {
+        let mut interner = Interner::default();
+
+        let a = interner.intern("hello");
+        let b = interner.concat("hel", "lo");
+
+        assert!(Rc::ptr_eq(&a, &b));
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn test_different_strings_do_not() {
+        let mut interner = Interner::default();
+
+        let a = interner.intern("hello");
+        let b = interner.intern("world");
+
+        assert!(!Rc::ptr_eq(&a, &b));
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_unused_strings_are_swept() {
+        let mut interner = Interner::default();
+
+        let kept = interner.intern("kept");
+        for i in 0..10 * INITIAL_SWEEP_AT {
+            interner.intern(&i.to_string());
+        }
+
+        assert!(interner.len() <= 2 * INITIAL_SWEEP_AT);
+        assert!(Rc::ptr_eq(&kept, &interner.intern("kept")));
+    }
+}
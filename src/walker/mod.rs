@@ -1,52 +1,215 @@
 mod ast;
+mod ast_json;
+mod interner;
 mod interpreter;
 mod parser;
+mod prelude;
 mod resolver;
 mod values;
 
 use std::{
     cell::RefCell,
-    io::{Read, Write},
+    io::{IsTerminal, Read, Write},
+    path::PathBuf,
+    rc::Rc,
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use colored::Colorize;
 use itertools::Itertools;
 use thiserror::Error;
+use typed_arena::Arena;
 
 use crate::{
-    shared::{scanner, streams::Streams},
-    walker::{interpreter::Interpreter, resolver::resolve},
+    shared::{
+        line_reader::{EditorLineReader, LineReader, StreamLineReader},
+        scanner,
+        scanner::{ScannerError, Token},
+        streams::Streams,
+    },
+    walker::{
+        ast::Stmt,
+        interpreter::{EvalArena, Interpreter, RuntimeError},
+        resolver::{resolutions, resolve, Locals},
+        values::Value,
+    },
 };
 
-pub fn exec(source: &str) -> Result<()> {
-    interpret(source, &RefCell::new(Streams::new()))?;
+/// Knobs for how strictly programs are checked before they run.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Options {
+    /// Report lint warnings, like an assignment used as a condition.
+    pub warnings: bool,
+    /// Require the bodies of `if`, `else`, `while`, and `for` to be blocks.
+    pub strict_braces: bool,
+    /// Report every variable lookup on the error stream.
+    pub trace: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            warnings: true,
+            strict_braces: false,
+            trace: false,
+        }
+    }
+}
+
+/// Run a whole program, returning the value of its last statement (as it would be printed)
+/// if that statement is an expression.
+pub fn exec(source: &str, options: Options) -> Result<Option<String>> {
+    Ok(interpret(source, &RefCell::new(Streams::new()), options)?)
+}
+
+/// Run a whole program, reading from and writing to the caller's streams.
+/// Since the streams are only borrowed for the duration of the run,
+/// the caller can inspect whatever their sinks collected as soon as this returns.
+/// If the program ends with an expression statement, its value is returned as it would be printed.
+pub fn run<I: Read, O: Write, E: Write>(
+    source: &str,
+    streams: &mut Streams<I, O, E>,
+    options: Options,
+) -> Result<Option<String>, InterpreterError> {
+    let streams = RefCell::new(Streams {
+        input: &mut streams.input,
+        output: &mut streams.output,
+        error: &mut streams.error,
+    });
+
+    interpret(source, &streams, options)
+}
+
+pub fn print_resolved_depths(source: &str) -> Result<()> {
+    resolved_depths(source, &RefCell::new(Streams::new()))?;
+
+    Ok(())
+}
+
+/// Print the syntax tree, either in its Lisp-ish debugging form or (if `json` is set) as JSON for tooling.
+pub fn print_ast(source: &str, options: Options, json: bool) -> Result<()> {
+    dump_ast(source, &RefCell::new(Streams::new()), options, json)?;
 
     Ok(())
 }
 
-pub fn repl() -> Result<()> {
-    println!("Gejang TW REPL");
+pub fn print_tokens(source: &str) -> Result<()> {
+    dump_tokens(source, &RefCell::new(Streams::new()))?;
+
+    Ok(())
+}
+
+pub fn test(scripts: &[PathBuf], options: Options) -> Result<()> {
+    let failures = run_tests(scripts, &RefCell::new(Streams::new()), options)?;
+
+    if failures > 0 {
+        bail!("{} of {} test scripts failed", failures, scripts.len());
+    }
+
+    Ok(())
+}
+
+pub fn print_features() -> Result<()> {
+    write_features(&RefCell::new(Streams::new()))?;
+
+    Ok(())
+}
+
+/// Start an interactive session.
+/// At a terminal, lines can be edited, and earlier ones recalled, even from previous sessions.
+pub fn repl(options: Options) -> Result<()> {
+    println!("Gejang TW REPL (type :help for a list of commands)");
+
+    let streams = RefCell::new(Streams::new());
+    if std::io::stdin().is_terminal() {
+        let history = dirs::config_dir().map(|d| d.join("gejang").join("history"));
+        run_repl(&streams, &mut EditorLineReader::new(history)?, options)
+    } else {
+        run_repl(&streams, &mut StreamLineReader::new(&streams), options)
+    }
+}
+
+/// The meta-commands the REPL understands, which are handled by the REPL instead of being run.
+const REPL_HELP: &str = "\
+:help        Show this list of commands
+:quit        Leave the REPL
+:clear       Forget every global defined so far
+:ast <code>  Print the syntax tree of <code> without running it";
 
+/// Read and run lines until the input runs out or the user enters `:quit`.
+fn run_repl<I: Read, O: Write, E: Write>(
+    streams: &RefCell<Streams<I, O, E>>,
+    lines: &mut impl LineReader,
+    options: Options,
+) -> Result<()> {
     let prefix = "🦀> ";
     let bad_prefix = "😵> ";
+    let continuation_prefix = "... ";
     let mut error = false;
+    let mut buffer = String::new();
 
-    let streams = RefCell::new(Streams::new());
+    // One arena serves every session, so that :clear doesn't abandon what earlier evals built.
+    let eval_arena = EvalArena::default();
+    let mut session = Session::new(streams, &eval_arena, options)?;
 
     loop {
-        write!(
-            streams.borrow_mut().output,
-            "{}",
-            if !error { prefix } else { bad_prefix }
+        let line = lines.read_line(
+            if !buffer.is_empty() {
+                continuation_prefix
+            } else if !error {
+                prefix
+            } else {
+                bad_prefix
+            },
         )?;
-        streams.borrow_mut().output.flush()?;
+        let Some(line) = line else {
+            writeln!(streams.borrow_mut().output)?;
+            if !buffer.is_empty() {
+                // Let the parser report whatever was left unfinished.
+                let _ = session.run(&buffer);
+            }
+            return Ok(());
+        };
+
+        // Lines starting with `:` are commands for the REPL itself,
+        // unless they continue an unfinished statement.
+        if let Some(command) = line.trim().strip_prefix(':').filter(|_| buffer.is_empty()) {
+            let (name, rest) = command.split_once(' ').unwrap_or((command, ""));
+            error = false;
+            match name {
+                "quit" => return Ok(()),
+                "help" => writeln!(streams.borrow_mut().output, "{REPL_HELP}")?,
+                "clear" => session = Session::new(streams, &eval_arena, options)?,
+                "ast" => error = session.ast(rest).is_err(),
+                _ => {
+                    writeln!(
+                        streams.borrow_mut().error,
+                        "{}",
+                        format!("Unknown command :{name}, enter :help for a list of commands")
+                            .red()
+                    )?;
+                    error = true
+                }
+            }
+            continue;
+        }
 
-        let mut buffer = String::new();
-        streams.borrow_mut().input.read_line(&mut buffer)?;
+        // Keep reading while the input so far is an unfinished statement,
+        // unless the user gives up on it by entering a blank line.
+        let abandoned = !buffer.is_empty() && line.trim().is_empty();
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if !abandoned && is_unfinished(&buffer) {
+            continue;
+        }
 
-        match interpret(&buffer, &streams) {
+        match session.run(&std::mem::take(&mut buffer)) {
             Ok(_) => error = false,
+            Err(e @ InterpreterError::Exit { .. }) => return Err(e.into()),
+            Err(e @ InterpreterError::AssertionFailed { .. }) => {
+                writeln!(streams.borrow_mut().error, "{}", e.to_string().red())?;
+                error = true
+            }
             Err(_) => error = true,
         }
     }
@@ -64,14 +227,112 @@ pub enum InterpreterError {
     Evaluation,
     #[error("Internal error")]
     Internal,
+    #[error("{msg}")]
+    AssertionFailed { msg: String },
+    #[error("Exited with code {code}")]
+    Exit { code: i32 },
 }
 
-fn interpret<I: Read, O: Write, E: Write>(
-    source: &str,
+/// Run each script in turn, reporting whether it passed or failed,
+/// and return the number of failures.
+fn run_tests<I: Read, O: Write, E: Write>(
+    scripts: &[PathBuf],
     streams: &RefCell<Streams<I, O, E>>,
-) -> Result<(), InterpreterError> {
+    options: Options,
+) -> Result<usize> {
+    let mut failures = 0;
+
+    for path in scripts {
+        // A script that can't be read fails, but doesn't stop the rest from running.
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                failures += 1;
+                writeln!(
+                    streams.borrow_mut().output,
+                    "{} {}: {}",
+                    "FAIL".red(),
+                    path.display(),
+                    e
+                )?;
+                continue;
+            }
+        };
+
+        match interpret(&source, streams, options) {
+            Ok(_) | Err(InterpreterError::Exit { code: 0 }) => writeln!(
+                streams.borrow_mut().output,
+                "{} {}",
+                "PASS".green(),
+                path.display()
+            )?,
+            Err(e) => {
+                failures += 1;
+                writeln!(
+                    streams.borrow_mut().output,
+                    "{} {}: {}",
+                    "FAIL".red(),
+                    path.display(),
+                    e
+                )?
+            }
+        }
+    }
+
+    Ok(failures)
+}
+
+/// The language capabilities this build of the tree-walking interpreter supports.
+fn features() -> Vec<&'static str> {
+    let mut features = vec![
+        "closures",
+        "classes",
+        "inheritance",
+        "lists",
+        "maps",
+        "slicing",
+        "switch",
+        "string-escapes",
+        "block-comments",
+        "doc-comments",
+        "eval",
+        "assert",
+        "exit",
+        "bitwise-operators",
+        "for-in",
+        "prelude",
+        "lint-warnings",
+    ];
+
+    if cfg!(debug_assertions) {
+        features.push("debug-assertions");
+    }
+
+    features
+}
+
+fn write_features<I: Read, O: Write, E: Write>(streams: &RefCell<Streams<I, O, E>>) -> Result<()> {
+    for feature in features() {
+        writeln!(streams.borrow_mut().output, "{feature}")?;
+    }
+
+    Ok(())
+}
+
+fn scan<'s, I: Read, O: Write, E: Write>(
+    source: &'s str,
+    streams: &RefCell<Streams<I, O, E>>,
+) -> Result<Vec<Token<'s>>, InterpreterError> {
     let (tokens, errors): (Vec<_>, Vec<_>) = scanner::scan(source).partition_result();
+    report_scan_errors(errors, streams)?;
+
+    Ok(tokens)
+}
 
+fn report_scan_errors<I: Read, O: Write, E: Write>(
+    errors: Vec<ScannerError>,
+    streams: &RefCell<Streams<I, O, E>>,
+) -> Result<(), InterpreterError> {
     if !errors.is_empty() {
         for e in errors {
             writeln!(streams.borrow_mut().error, "{}", e.to_string().red())
@@ -80,8 +341,61 @@ fn interpret<I: Read, O: Write, E: Write>(
         return Err(InterpreterError::Scanner);
     }
 
-    let (statements, errors): (Vec<_>, Vec<_>) =
-        parser::parse(tokens.iter()).into_iter().partition_result();
+    Ok(())
+}
+
+/// Scan and parse a whole program, handing each token to the parser as soon as it is scanned.
+/// The syntax tree borrows the tokens, so they go into `tokens`, which unlike a `Vec`
+/// never copies them as it grows. Scan errors are still reported all together, instead of parse errors.
+fn scan_and_parse<'s, I: Read, O: Write, E: Write>(
+    source: &'s str,
+    tokens: &'s Arena<Token<'s>>,
+    streams: &RefCell<Streams<I, O, E>>,
+    options: Options,
+) -> Result<Vec<Stmt<'s>>, InterpreterError> {
+    let scan_errors = Rc::new(RefCell::new(Vec::new()));
+    let scanned = {
+        let scan_errors = Rc::clone(&scan_errors);
+        scanner::scan(source).filter_map(move |result| match result {
+            Ok(token) => Some(&*tokens.alloc(token)),
+            Err(e) => {
+                scan_errors.borrow_mut().push(e);
+                None
+            }
+        })
+    };
+
+    let results = parse_results(scanned, options);
+    report_scan_errors(scan_errors.take(), streams)?;
+
+    statements_or_report(results, streams)
+}
+
+fn parse<'s, I: Read, O: Write, E: Write>(
+    tokens: &'s [Token<'s>],
+    streams: &RefCell<Streams<I, O, E>>,
+    options: Options,
+) -> Result<Vec<Stmt<'s>>, InterpreterError> {
+    statements_or_report(parse_results(tokens, options), streams)
+}
+
+fn parse_results<'s, T>(tokens: T, options: Options) -> Vec<parser::ParserStmtResult<'s>>
+where
+    T: IntoIterator<Item = &'s Token<'s>>,
+    T::IntoIter: 's,
+{
+    if options.strict_braces {
+        parser::parse_strict(tokens)
+    } else {
+        parser::parse(tokens)
+    }
+}
+
+fn statements_or_report<'s, I: Read, O: Write, E: Write>(
+    results: Vec<parser::ParserStmtResult<'s>>,
+    streams: &RefCell<Streams<I, O, E>>,
+) -> Result<Vec<Stmt<'s>>, InterpreterError> {
+    let (statements, errors): (Vec<_>, Vec<_>) = results.into_iter().partition_result();
 
     if !errors.is_empty() {
         for e in errors {
@@ -91,35 +405,383 @@ fn interpret<I: Read, O: Write, E: Write>(
         return Err(InterpreterError::Parser);
     }
 
-    let locals = resolve(&statements)
+    Ok(statements)
+}
+
+/// Whether `source` is a prefix of a statement that more lines could finish,
+/// like a block whose closing brace hasn't been typed yet.
+fn is_unfinished(source: &str) -> bool {
+    let Ok(tokens) = scanner::scan(source).collect::<Result<Vec<_>, _>>() else {
+        return false;
+    };
+
+    parser::parse(tokens.iter())
+        .into_iter()
+        .any(|r| matches!(r, Err(parser::ParserError::UnexpectedEndOfInput)))
+}
+
+fn resolved_depths<I: Read, O: Write, E: Write>(
+    source: &str,
+    streams: &RefCell<Streams<I, O, E>>,
+) -> Result<(), InterpreterError> {
+    let tokens = Arena::new();
+    let statements = scan_and_parse(source, &tokens, streams, Options::default())?;
+
+    let resolutions = resolutions(&statements)
         .inspect_err(|e| {
             writeln!(streams.borrow_mut().error, "{}", e.to_string().red())
                 .expect("Failed to write error");
         })
         .map_err(|_| InterpreterError::Resolver)?;
 
-    let interpreter = Interpreter::new(streams, locals);
+    for r in resolutions {
+        writeln!(streams.borrow_mut().output, "{}", r).map_err(|_| InterpreterError::Internal)?;
+    }
 
-    interpreter.interpret(&statements).map_err(|e| {
-        if writeln!(streams.borrow_mut().error, "{}", e.to_string().red()).is_err() {
-            InterpreterError::Internal
-        } else {
-            InterpreterError::Evaluation
+    Ok(())
+}
+
+/// Write each token (including comments) with the line it is on, one per line,
+/// stopping at the first scanner error.
+fn dump_tokens<I: Read, O: Write, E: Write>(
+    source: &str,
+    streams: &RefCell<Streams<I, O, E>>,
+) -> Result<(), InterpreterError> {
+    for token in scanner::scan(source) {
+        match token {
+            Ok(t) => writeln!(
+                streams.borrow_mut().output,
+                "{}: {} {:?}",
+                t.line,
+                t.typ,
+                t.lexeme
+            )
+            .map_err(|_| InterpreterError::Internal)?,
+            Err(e) => {
+                writeln!(streams.borrow_mut().error, "{}", e.to_string().red())
+                    .map_err(|_| InterpreterError::Internal)?;
+                return Err(InterpreterError::Scanner);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the syntax tree of each statement, one per line, without running anything.
+fn dump_ast<I: Read, O: Write, E: Write>(
+    source: &str,
+    streams: &RefCell<Streams<I, O, E>>,
+    options: Options,
+    json: bool,
+) -> Result<(), InterpreterError> {
+    let tokens = Arena::new();
+    let statements = scan_and_parse(source, &tokens, streams, options)?;
+
+    if json {
+        writeln!(
+            streams.borrow_mut().output,
+            "{}",
+            ast_json::to_json(&statements)
+        )
+        .map_err(|_| InterpreterError::Internal)?;
+        return Ok(());
+    }
+
+    for s in statements {
+        writeln!(streams.borrow_mut().output, "{}", s).map_err(|_| InterpreterError::Internal)?;
+    }
+
+    Ok(())
+}
+
+/// If `options.warnings` is set, lint warnings are reported on the error stream
+/// (but never stop the program from running).
+fn resolve_and_warn<'s, I: Read, O: Write, E: Write>(
+    statements: &'s [Stmt<'s>],
+    streams: &RefCell<Streams<I, O, E>>,
+    options: Options,
+) -> Result<Locals<'s>, InterpreterError> {
+    let (locals, lints) = resolve(statements)
+        .inspect_err(|e| {
+            writeln!(streams.borrow_mut().error, "{}", e.to_string().red())
+                .expect("Failed to write error");
+        })
+        .map_err(|_| InterpreterError::Resolver)?;
+
+    if options.warnings {
+        for w in lints {
+            writeln!(streams.borrow_mut().error, "{}", w.to_string().yellow())
+                .map_err(|_| InterpreterError::Internal)?;
         }
-    })
+    }
+
+    Ok(locals)
+}
+
+fn report_runtime_error<I: Read, O: Write, E: Write>(
+    e: RuntimeError,
+    streams: &RefCell<Streams<I, O, E>>,
+) -> InterpreterError {
+    match e {
+        // Assertion failures are reported by whoever is running the script (e.g., the test harness)
+        e @ RuntimeError::AssertionFailed { .. } => {
+            InterpreterError::AssertionFailed { msg: e.to_string() }
+        }
+        // Likewise, it's up to them whether exiting ends the process.
+        RuntimeError::Exit { code } => InterpreterError::Exit { code },
+        e => {
+            if writeln!(streams.borrow_mut().error, "{}", e.to_string().red()).is_err() {
+                InterpreterError::Internal
+            } else {
+                InterpreterError::Evaluation
+            }
+        }
+    }
+}
+
+/// Run a whole program, returning the printed value of its final expression statement, if any.
+fn interpret<I: Read, O: Write, E: Write>(
+    source: &str,
+    streams: &RefCell<Streams<I, O, E>>,
+    options: Options,
+) -> Result<Option<String>, InterpreterError> {
+    let tokens = Arena::new();
+    let statements = scan_and_parse(source, &tokens, streams, options)?;
+    let locals = resolve_and_warn(&statements, streams, options)?;
+
+    let eval_arena = EvalArena::default();
+    let interpreter = Interpreter::new(streams, &eval_arena, options.trace);
+    load_prelude(&interpreter)?;
+
+    interpreter
+        .interpret(&statements, locals)
+        .map(|value| value.map(|v| v.borrow().to_string()))
+        .map_err(|e| report_runtime_error(e, streams))
+}
+
+/// Define the prelude's functions as globals.
+/// The prelude is part of the interpreter rather than the user's program,
+/// so any problem with it is an internal error, and its warnings are never shown.
+fn load_prelude<I: Read, O: Write, E: Write>(
+    interpreter: &Interpreter<'_, '_, I, O, E>,
+) -> Result<(), InterpreterError> {
+    let statements = prelude::statements().ok_or(InterpreterError::Internal)?;
+    let (locals, _) = resolve(statements).map_err(|_| InterpreterError::Internal)?;
+
+    interpreter
+        .interpret(statements, locals)
+        .map(|_| ())
+        .map_err(|_| InterpreterError::Internal)
+}
+
+/// A REPL session, which keeps its globals from one line to the next.
+struct Session<'s, 'io, I: Read, O: Write, E: Write> {
+    interpreter: Interpreter<'s, 'io, I, O, E>,
+    streams: &'io RefCell<Streams<I, O, E>>,
+    options: Options,
+}
+
+impl<'s, 'io, I: Read, O: Write, E: Write> Session<'s, 'io, I, O, E> {
+    fn new(
+        streams: &'io RefCell<Streams<I, O, E>>,
+        eval_arena: &'s EvalArena<'s>,
+        options: Options,
+    ) -> Result<Self, InterpreterError> {
+        let interpreter = Interpreter::new(streams, eval_arena, options.trace);
+        load_prelude(&interpreter)?;
+
+        Ok(Session {
+            interpreter,
+            streams,
+            options,
+        })
+    }
+
+    /// Run one line of input.
+    /// Anything the line defines can be used by later lines, so its source and syntax tree are leaked
+    /// to live as long as the session.
+    ///
+    /// A line that is just an expression (with or without its semicolon) has its value echoed,
+    /// unless that value is `nil`.
+    fn run(&self, line: &str) -> Result<(), InterpreterError> {
+        let source: &'static str = Box::leak(line.to_string().into_boxed_str());
+        let tokens: &'static [Token] = Box::leak(scan(source, self.streams)?.into_boxed_slice());
+        let statements = match parser::parse_expression(tokens.iter()) {
+            Ok(expr) => vec![Stmt::Expression {
+                expr: Box::new(expr),
+            }],
+            Err(_) => parse(tokens, self.streams, self.options)?,
+        };
+        let statements: &'static [Stmt] = Box::leak(statements.into_boxed_slice());
+        let locals = resolve_and_warn(statements, self.streams, self.options)?;
+
+        let value = self
+            .interpreter
+            .interpret(statements, locals)
+            .map_err(|e| {
+                // A statement that failed partway through a block can leave its scopes behind.
+                self.interpreter.reset_scopes();
+                report_runtime_error(e, self.streams)
+            })?;
+
+        if let ([Stmt::Expression { .. }], Some(value)) = (statements, value) {
+            if !matches!(*value.borrow(), Value::Nil) {
+                writeln!(self.streams.borrow_mut().output, "{}", value.borrow())
+                    .map_err(|_| InterpreterError::Internal)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print the syntax tree of a line without running it.
+    fn ast(&self, line: &str) -> Result<(), InterpreterError> {
+        let tokens = scan(line, self.streams)?;
+        match parser::parse_expression(tokens.iter()) {
+            Ok(expr) => writeln!(self.streams.borrow_mut().output, "{}", expr)
+                .map_err(|_| InterpreterError::Internal),
+            Err(_) => dump_ast(line, self.streams, self.options, false),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate test;
+
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
     use rstest::rstest;
+    use test::Bencher;
 
     use super::*;
 
+    /// Counts the allocations made on each thread, so tests can check how much code allocates
+    /// without being thrown off by tests running on other threads.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+        /// How many bytes this thread has allocated and not yet freed, and the most there have been.
+        /// A block freed by a different thread than allocated it can make this go negative.
+        static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+        static PEAK_BYTES: Cell<isize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            let _ = LIVE_BYTES.try_with(|live| {
+                live.set(live.get() + layout.size() as isize);
+                let _ = PEAK_BYTES.try_with(|peak| peak.set(peak.get().max(live.get())));
+            });
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            let _ = LIVE_BYTES.try_with(|live| live.set(live.get() - layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// How many allocations running the program makes.
+    fn allocations(source: &str) -> usize {
+        let streams = RefCell::new(Streams::test());
+        let before = ALLOCATIONS.with(Cell::get);
+        interpret(source, &streams, Options::default()).unwrap();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    /// The most bytes `f` had allocated at once, on top of what was already allocated when it started.
+    fn peak_bytes<T>(f: impl FnOnce() -> T) -> usize {
+        let start = LIVE_BYTES.with(Cell::get);
+        PEAK_BYTES.with(|peak| peak.set(start));
+        let result = f();
+        let peak = PEAK_BYTES.with(Cell::get) - start;
+        drop(result);
+        peak as usize
+    }
+
+    fn large_file() -> String {
+        (0..2000)
+            .map(|i| {
+                format!("/// Doc for f{i}.\nfun f{i}(a, b) {{ // add\n  return a + b * {i}; }}\n")
+            })
+            .join("")
+    }
+
+    #[test]
+    fn test_scanning_into_an_arena_lowers_peak_memory() {
+        // Collecting the tokens into a Vec before parsing holds on to spare capacity and the copies made while growing,
+        // while the arena only ever holds each token once.
+        let source = large_file();
+        let streams = RefCell::new(Streams::test());
+
+        let collected = peak_bytes(|| {
+            let tokens = scan(&source, &streams).unwrap();
+            parse(&tokens, &streams, Options::default()).unwrap().len()
+        });
+        let arena = peak_bytes(|| {
+            let tokens = Arena::new();
+            scan_and_parse(&source, &tokens, &streams, Options::default())
+                .unwrap()
+                .len()
+        });
+
+        assert!(
+            arena < collected,
+            "arena peak {arena} bytes, collected peak {collected} bytes"
+        );
+    }
+
     #[rstest]
     #[case("print 1 + 2;", "3\n")]
     #[case("print 2 * 4 + 3;", "11\n")]
+    #[case(
+        "class A {} var a = A(); var b = a; a.field = \"x\"; print b.field; b.field = \"y\"; print a.field;",
+        "x\ny\n"
+    )]
+    #[case("print range(3);", "[0, 1, 2]\n")]
+    #[case("print range(2, 5);", "[2, 3, 4]\n")]
+    #[case("print range(0); print range(5, 2);", "[]\n[]\n")]
+    #[case("print type(1);", "number\n")]
+    #[case("print type(\"a\");", "string\n")]
+    #[case("print type(true);", "boolean\n")]
+    #[case("print type(nil);", "nil\n")]
+    #[case("fun f() {} print type(f);", "function\n")]
+    #[case("print type(clock);", "function\n")]
+    #[case(
+        "class A { m() {} } print type(A); print type(A()); print type(A().m);",
+        "class\ninstance\nfunction\n"
+    )]
+    #[case("print type([1]); print type({\"a\": 1});", "list\nmap\n")]
+    #[case("print type(type(1));", "string\n")]
+    #[case("print max(3, 7);", "7\n")]
+    #[case("print min(3, 7);", "3\n")]
+    #[case(
+        "print clamp(12, 0, 10); print clamp(-2, 0, 10); print clamp(5, 0, 10);",
+        "10\n0\n5\n"
+    )]
+    #[case("fun max(a, b) { return \"mine\"; } print max(1, 2);", "mine\n")]
+    #[case("print 6 & 3;", "2\n")]
+    #[case("print 4 | 1;", "5\n")]
+    #[case("print 5 ^ 1;", "4\n")]
+    #[case("print -1 & 255;", "255\n")]
+    #[case("print 1 | 6 & 3 ^ 1;", "3\n")]
+    #[case("print 1 | 2 == 3;", "true\n")]
+    #[case("print 1 + 2 & 6;", "2\n")]
     #[case("print true;", "true\n")]
     #[case("print \"one\";", "one\n")]
+    #[case("print 1, \"two\", [3];", "1 two [3]\n")]
+    #[case("print;", "\n")]
+    #[case("var a = 1; print a, a + 1, nil;", "1 2 nil\n")]
     #[case("var foo = \"bar\"; print foo;", "bar\n")]
     #[case("var foo = 1 + 2 * 6; print foo;", "13\n")]
     #[case("var foo; print foo;", "nil\n")]
@@ -184,9 +846,111 @@ global c
         "for (var i = 0; i < 3; i = i + 1) {print i; if (i >= 1) break;}",
         "0\n1\n"
     )]
+    #[case(
+        "for (var i = 0; i < 3; i = i + 1) {if (i == 1) continue; print i;}",
+        "0\n2\n"
+    )]
+    #[case(
+        "for (var i = 0; i < 3; i = i + 1) {for (var j = 0; j < 3; j = j + 1) {if (j == 1) continue; print i * 10 + j;} if (i == 0) continue; print i;}",
+        "0\n2\n10\n12\n1\n20\n22\n2\n"
+    )]
+    #[case(
+        "var i = 0; while (i < 4) {i = i + 1; if (i == 2) continue; print i;}",
+        "1\n3\n4\n"
+    )]
+    #[case(
+        "for (var i = 0; i < 3; i = i + 1) {fun f() {return i;} if (i == 1) continue; print f();}",
+        "0\n2\n"
+    )]
+    #[case(
+        "var i; for (i = 0; i < 10; i = i + 1) {if (i == 1) continue; if (i == 3) break; print i;} print i;",
+        "0\n2\n3\n"
+    )]
+    #[case(
+        "var n = 0; for (var i = 0; i < 5; n = n + 1) {i = i + 1; if (i < 3) continue; if (i == 4) break;} print n;",
+        "3\n"
+    )]
     #[case("var i = 0; for (; i < 3; i = i + 1) print i;", "0\n1\n2\n")]
     #[case("for (var i = 0; i < 3;) {print i; i = i + 1;}", "0\n1\n2\n")]
     #[case("var i = 0; for (; i < 3;) {print i; i = i + 1;}", "0\n1\n2\n")]
+    #[case("var sum = 0; for (x in [1, 2, 3]) sum = sum + x; print sum;", "6\n")]
+    #[case("for (x in []) print x; print \"done\";", "done\n")]
+    #[case("for (x in [1, 2, 3]) {if (x == 2) continue; print x;}", "1\n3\n")]
+    #[case("for (x in [1, 2, 3]) {if (x == 2) break; print x;}", "1\n")]
+    #[case("var x = 0; for (x in [1, 2]) {} print x;", "0\n")]
+    #[case(
+        "var xs = [1, 2]; for (x in xs) push(xs, x); print xs;",
+        "[1, 2, 1, 2]\n"
+    )]
+    #[case(
+        "var fs = []; for (x in [1, 2]) {fun f() {return x;} push(fs, f);} print fs[0]() + fs[1]();",
+        "3\n"
+    )]
+    #[case(
+        "var a = 1 < 2; var b = 1 < 2; a = false; print a; print b;",
+        "false\ntrue\n"
+    )]
+    #[case("var a = nil; var b; a = 1; print a; print b;", "1\nnil\n")]
+    #[case(
+        "class C {} var c = C(); c.f = 1 == 1; c.f = 0; print c.f; print !false;",
+        "0\ntrue\n"
+    )]
+    #[case("print (1 < 2) == true; print !nil == (2 >= 2);", "true\ntrue\n")]
+    #[case(
+        "class A { m() { return 1; } } var a = A(); print a.m(); a.m = 2; print a.m; print A().m();",
+        "1\n2\n1\n"
+    )]
+    #[case(
+        "class A {} fun f() { return 3; } var a = A(); a.g = f; print a.g();",
+        "3\n"
+    )]
+    #[case(
+        "class Foo { init() { this.n = 1; } } var a = Foo(); print a.init(); print a.init() == a;",
+        "<instance of <cls Foo>>\ntrue\n"
+    )]
+    #[case(
+        "class Foo { init(x) { this.x = x; if (x > 0) return; this.x = 0; } } var a = Foo(1); print a.init(2).x; print a.init(-1).x;",
+        "2\n0\n"
+    )]
+    #[case(
+        "class A {} var a = A(); var b = A(); print a == a; print a == b; print a != b; print A == A;",
+        "true\nfalse\ntrue\ntrue\n"
+    )]
+    #[case(
+        "fun f() {} fun g() {} print f == f; print f == g; print clock == clock;",
+        "true\nfalse\ntrue\n"
+    )]
+    #[case(
+        "print split(\"a,b\", \",\") == split(\"a,b\", \",\"); print split(\"a\", \",\") == split(\"b\", \",\");",
+        "true\nfalse\n"
+    )]
+    #[case(
+        "print 1 == \"1\"; print nil == false; print nil == nil;",
+        "false\nfalse\ntrue\n"
+    )]
+    #[case(
+        "class Circle { init(r) { this.r = r; } area { return 3 * this.r * this.r; } } var c = Circle(2); print c.area; c.r = 1; print c.area;",
+        "12\n3\n"
+    )]
+    #[case(
+        "class Counter { init() { this.n = 0; } next { this.n = this.n + 1; return this.n; } } var c = Counter(); c.next; print c.next;",
+        "2\n"
+    )]
+    #[case(
+        "class A { name { return \"A\"; } } class B < A {} print B().name;",
+        "A\n"
+    )]
+    #[case(
+        "class A { name { return \"A\"; } } class B < A { name { return super.name + \"B\"; } } print B().name;",
+        "AB\n"
+    )]
+    #[case(
+        "class C { init() { this.n = 1; } inc() { this.n = this.n + 1; } } var c = C(); c.inc(); print c.n;",
+        "2\n"
+    )]
+    #[case("var x; x = 3; print x;", "3\n")]
+    #[case("var x = 1; { var x = 2; x = 3; print x; } print x;", "3\n1\n")]
+    #[case("var x = 1; fun f() { x = 2; } f(); print x;", "2\n")]
     #[case("print clock;", "<native fun clock/0>\n")]
     #[case("print tsp2cup(15);", "0.3125\n")]
     #[case(
@@ -259,7 +1023,7 @@ class Foo {
   }
 }
 
-foo = Foo();
+var foo = Foo();
 print(foo);
 
 foo.field = "goodbye";
@@ -295,26 +1059,1110 @@ print(Foo("bar").foo);
 "#,
         "bar\n"
     )]
-    fn test_interpreter(#[case] source: &str, #[case] expected: &str) {
-        println!("source:\n{}", source);
-        let streams = RefCell::new(Streams::test());
-        let r = interpret(source, &streams);
-        println!("stdout:\n{}", streams.borrow().get_output().unwrap());
-        println!("stderr:\n{}", streams.borrow().get_error().unwrap());
-        r.unwrap();
-        assert_eq!(streams.borrow().get_output().unwrap(), expected);
-    }
-
-    #[test]
-    fn test_clock() {
-        let source = "print clock();";
-        println!("source:\n{}", source);
-        let streams = RefCell::new(Streams::test());
-        let r = interpret(source, &streams);
-        println!("stdout:\n{}", streams.borrow().get_output().unwrap());
-        println!("stderr:\n{}", streams.borrow().get_error().unwrap());
-        r.unwrap();
+    #[case("print chars(\"a🦀b\");", "[a, 🦀, b]\n")]
+    #[case("print chars(\"\");", "[]\n")]
+    #[case("print fmtNumber(3.1, 3);", "3.100\n")]
+    #[case("print fmtNumber(2, 2);", "2.00\n")]
+    #[case("print fmtNumber(2.567, 0);", "3\n")]
+    #[case("print fmtNumber(1, 2) + \"!\";", "1.00!\n")]
+    #[case("print len(fmtNumber(1, 100));", "102\n")]
+    #[case("fun add(a, b) { return a + b; } print add(1, 2);", "3\n")]
+    #[case(
+        "{ var a = \"outer\"; { var a = \"inner\"; print a; } print a; }",
+        "inner\nouter\n"
+    )]
+    #[case("var xs = chars(\"abcde\"); print xs[1:3];", "[b, c]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[:2];", "[a, b]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[2:];", "[c, d, e]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[:];", "[a, b, c, d, e]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[-2:];", "[d, e]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[3:100];", "[d, e]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[-100:1];", "[a]\n")]
+    #[case("var xs = chars(\"abcde\"); print xs[4:2];", "[]\n")]
+    #[case("print \"a🦀bcd\"[1:3];", "🦀b\n")]
+    #[case("print []; print [1, \"two\", nil, [3]];", "[]\n[1, two, nil, [3]]\n")]
+    #[case(
+        "var xs = [1, 2, 3,]; print xs[0]; print xs[2]; print xs[-1]; print xs[-3];",
+        "1\n3\n3\n1\n"
+    )]
+    #[case(
+        "var xs = [1, 2, 3]; xs[1] = 20; xs[-1] = xs[0] + 29; print xs;",
+        "[1, 20, 30]\n"
+    )]
+    #[case("var xs = [1, 2]; print xs[0] = 5;", "5\n")]
+    #[case(
+        "var xs = [[1, 2], [3, 4]]; xs[1][0] = 30; print xs[1][0]; print xs;",
+        "30\n[[1, 2], [30, 4]]\n"
+    )]
+    #[case(
+        "var xs = []; push(xs, 1); push(xs, \"b\"); print xs; print len(xs);",
+        "[1, b]\n2\n"
+    )]
+    #[case(
+        "var xs = [1, 2, 3]; print pop(xs); print xs; print len(xs);",
+        "3\n[1, 2]\n2\n"
+    )]
+    #[case("var xs = [1]; var ys = xs; push(ys, 2); print xs;", "[1, 2]\n")]
+    #[case(
+        "fun first(xs) { return xs[0]; } print first([\"a\", \"b\"])[0:1];",
+        "a\n"
+    )]
+    #[case("var xs = [1, 2, 3]; print xs[1:][0];", "2\n")]
+    #[case(
+        "var x = 2; switch (x) { case 1: print \"one\"; case 2: print \"two\"; print \"!\"; case 3: print \"three\"; }",
+        "two\n!\n"
+    )]
+    #[case(
+        "switch (\"z\") { case \"a\": print 1; default: print \"other\"; }",
+        "other\n"
+    )]
+    #[case(
+        "switch (4) { case 1: print 1; case 2: print 2; } print \"done\";",
+        "done\n"
+    )]
+    #[case("switch (1) {} print \"empty\";", "empty\n")]
+    #[case(
+        "switch (1) { default: print \"default\"; case 1: print \"one\"; }",
+        "one\n"
+    )]
+    #[case(
+        "fun f() { print \"evaluated\"; return 2; } switch (1) { case 1: print 1; case f(): print 2; }",
+        "1\n"
+    )]
+    #[case(
+        "var x = \"outer\"; switch (1) { case 1: var x = \"inner\"; print x; } print x;",
+        "inner\nouter\n"
+    )]
+    #[case(
+        "for (var i = 0; i < 4; i = i + 1) { switch (i) { case 1: continue; case 3: break; } print i; }",
+        "0\n2\n"
+    )]
+    #[case(
+        "fun sign(n) { switch (true) { case n < 0: return -1; case n > 0: return 1; } return 0; } print sign(-5); print sign(0);",
+        "-1\n0\n"
+    )]
+    #[case(
+        "print {}; print {\"b\": 2, 10: \"ten\", \"a\": 1, 2: nil,};",
+        "{}\n{2: nil, 10: ten, a: 1, b: 2}\n"
+    )]
+    #[case("var m = {\"k\": 1, 2: \"two\"}; print m[\"k\"]; print m[2]; print m[\"missing\"]; print m[3];", "1\ntwo\nnil\nnil\n")]
+    #[case(
+        "var m = {\"k\": 1}; m[\"k\"] = 2; m[\"j\"] = 3; print m; print m[\"k\"];",
+        "{j: 3, k: 2}\n2\n"
+    )]
+    #[case("var m = {0: \"zero\"}; print m[-0]; print m[0.0];", "zero\nzero\n")]
+    #[case(
+        "var m = {\"b\": 1, \"a\": 2, 1: 3}; print keys(m); print keys({});",
+        "[1, a, b]\n[]\n"
+    )]
+    #[case(
+        "var m = {\"a\": nil}; print has(m, \"a\"); print has(m, \"b\"); print has(m, nil);",
+        "true\nfalse\nfalse\n"
+    )]
+    #[case(
+        "var m = {\"xs\": [1]}; push(m[\"xs\"], 2); m[\"n\"] = {}; m[\"n\"][\"x\"] = 1; print m;",
+        "{n: {x: 1}, xs: [1, 2]}\n"
+    )]
+    #[case(
+        "print {\"a\": 1, 2: [3]} == {2: [3], \"a\": 1}; print {\"a\": 1} == {\"a\": 2};",
+        "true\nfalse\n"
+    )]
+    #[case(
+        "var a = [1]; a[0] = a; var b = [1]; b[0] = b; print a == a; print a == b; print a == [a];",
+        "true\ntrue\ntrue\n"
+    )]
+    #[case(
+        "var a = [1, 2]; a[0] = a; var b = [1, 3]; b[0] = b; print a == b;",
+        "false\n"
+    )]
+    #[case(
+        "var l = [1]; push(l, l); print l; print [l];",
+        "[1, [...]]\n[[1, [...]]]\n"
+    )]
+    #[case("var l = [1]; print [l, l];", "[[1], [1]]\n")]
+    #[case(
+        "var m = {}; m[\"self\"] = m; var n = {}; n[\"self\"] = n; print m == n; n[\"x\"] = 1; print m == n;",
+        "true\nfalse\n"
+    )]
+    #[case(
+        "var m = {\"a\": 1}; m[\"b\"] = m; print m; print [m];",
+        "{a: 1, b: {...}}\n[{a: 1, b: {...}}]\n"
+    )]
+    #[case(
+        "var m = {\"a\": 1}; var l = [m]; m[\"l\"] = l; print l;",
+        "[{a: 1, l: [...]}]\n"
+    )]
+    #[case("print \"hello\"[:2] + \"hello\"[3:];", "helo\n")]
+    #[case(
+        "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(15);",
+        "610\n"
+    )]
+    #[case(
+        "fun counter() { var n = 0; fun count() { n = n + 1; return n; } return count; } var a = counter(); var b = counter(); a(); a(); print a(); print b();",
+        "3\n1\n"
+    )]
+    #[case(
+        "class Box { init(x) { this.x = x; } } var a = Box(1); var b = Box(2); print a.x; print b.x; print a;",
+        "1\n2\n<instance of <cls Box>>\n"
+    )]
+    #[case(
+        "var a = true; var b = true; if (a) if (b) print 1; else print 2;",
+        "1\n"
+    )]
+    #[case(
+        "var a = true; var b = false; if (a) if (b) print 1; else print 2;",
+        "2\n"
+    )]
+    #[case(
+        "var a = false; var b = true; if (a) if (b) print 1; else print 2;",
+        ""
+    )]
+    #[case(
+        "var a = false; var b = false; if (a) if (b) print 1; else print 2;",
+        ""
+    )]
+    #[case(
+        "var a = false; var b = false; if (a) { if (b) print 1; } else print 2;",
+        "2\n"
+    )]
+    #[case(
+        "class P { init(x) { this.x = x; if (x > 0) return; this.x = -1; } } print P(5).x; print P(0).x;",
+        "5\n-1\n"
+    )]
+    #[case(
+        "class P { init() { return; } } fun make() { P(); return 2; } print make();",
+        "2\n"
+    )]
+    #[case(
+        "class A { hi() { print \"hi from A\"; } } class B < A {} B().hi();",
+        "hi from A\n"
+    )]
+    #[case(
+        "class A { name() { return \"A\"; } } class B < A { name() { return \"B\"; } } print B().name(); print A().name();",
+        "B\nA\n"
+    )]
+    #[case(
+        "class A { init(x) { this.x = x; } get() { return this.x; } } class B < A {} class C < B {} print C(3).get();",
+        "3\n"
+    )]
+    #[case(
+        "class A {} class B < A {} print B; print B();",
+        "<cls B>\n<instance of <cls B>>\n"
+    )]
+    #[case(
+        "class Animal { init(name) { this.name = name; } describe() { return \"an animal called \" + this.name; } } class Dog < Animal { describe() { return super.describe() + \", a dog\"; } } print Dog(\"Rex\").describe();",
+        "an animal called Rex, a dog\n"
+    )]
+    #[case(
+        "class A { method() { print \"A method\"; } } class B < A { method() { print \"B method\"; } test() { super.method(); } } class C < B {} C().test();",
+        "A method\n"
+    )]
+    #[case(
+        "class A { init(x) { this.x = x; } } class B < A { init(x) { super.init(x * 2); } } print B(2).x;",
+        "4\n"
+    )]
+    #[case(
+        "print \"apple\" < \"banana\"; print \"banana\" < \"apple\";",
+        "true\nfalse\n"
+    )]
+    #[case("print \"b\" > \"a\"; print \"a\" > \"a\";", "true\nfalse\n")]
+    #[case("print \"a\" <= \"a\"; print \"b\" <= \"a\";", "true\nfalse\n")]
+    #[case("print \"a\" >= \"a\"; print \"a\" >= \"ab\";", "true\nfalse\n")]
+    #[case("print \"Z\" < \"a\"; print \"\" < \"a\";", "true\ntrue\n")]
+    #[case("print startsWith(\"gejang\", \"ge\");", "true\n")]
+    #[case("print startsWith(\"gejang\", \"jang\");", "false\n")]
+    #[case("print startsWith(\"gejang\", \"\");", "true\n")]
+    #[case("print endsWith(\"gejang\", \"jang\");", "true\n")]
+    #[case("print endsWith(\"gejang\", \"ge\");", "false\n")]
+    #[case("print \"[\" + trim(\"  a b \\t\\n\") + \"]\";", "[a b]\n")]
+    #[case("print str(42) + \"!\";", "42!\n")]
+    #[case("print str(-0.5) + \" \" + str(1e3);", "-0.5 1000\n")]
+    #[case("print str(true) + str(false) + str(nil);", "truefalsenil\n")]
+    #[case(
+        "print str(\"already\") == \"already\"; print str(1) == \"1\";",
+        "true\ntrue\n"
+    )]
+    #[case(
+        "print num(\"3.14\"); print num(\"-2\") + 1; print num(\"1e3\");",
+        "3.14\n-1\n1000\n"
+    )]
+    #[case("print num(42); print num(num(\"7\")) == 7;", "42\ntrue\n")]
+    #[case("print num(str(0.25)) == 0.25;", "true\n")]
+    #[case(
+        "print sqrt(16); print sqrt(2) * sqrt(2) - 2 < 1e-9; print sqrt(-1);",
+        "4\ntrue\nNaN\n"
+    )]
+    #[case("print abs(-3.5); print abs(2); print abs(0);", "3.5\n2\n0\n")]
+    #[case("print floor(2.7); print floor(-2.2); print floor(3);", "2\n-3\n3\n")]
+    #[case("print ceil(2.2); print ceil(-2.7); print ceil(3);", "3\n-2\n3\n")]
+    #[case(
+        "print pow(2, 10); print pow(9, 0.5); print pow(2, -1);",
+        "1024\n3\n0.5\n"
+    )]
+    #[case("var n = 0/0; print n == n;", "false\n")]
+    #[case("var n = 0/0; print n != n;", "true\n")]
+    #[case("print 0/0 == 0/0; print sqrt(-1) != 1;", "false\ntrue\n")]
+    #[case(
+        "var n = 0/0; var m = n; print n == m; print 1/0 == 1/0;",
+        "false\ntrue\n"
+    )]
+    #[case("print 1 + /* hi */ 2;", "3\n")]
+    #[case("print 1 + 2; // trailing", "3\n")]
+    #[case("print 1 // mid-statement\n + 2;", "3\n")]
+    #[case("var /* name: */ x = [1, /* two, */ 3]; print x;", "[1, 3]\n")]
+    #[case("/// docs for nothing\nprint 4;", "4\n")]
+    #[case("print 3.0; print 10 / 2; print 10 / 4;", "3\n5\n2.5\n")]
+    #[case(
+        "print 1000000; print 0.0000001; print 1 / 3;",
+        "1000000\n1e-7\n0.3333333333333333\n"
+    )]
+    #[case("write(\"a\"); write(\"b\");", "ab")]
+    #[case("write(1); write(nil); print \"!\";", "1nil!\n")]
+    #[case("print write(\"x\");", "xnil\n")]
+    #[case("print len(\"gejang\");", "6\n")]
+    #[case("print len(\"\");", "0\n")]
+    #[case("print len(\"안녕\"); print len(\"a🦀b\");", "2\n3\n")]
+    #[case("print substr(\"gejang\", 2, 6);", "jang\n")]
+    #[case("print substr(\"gejang\", 0, 2);", "ge\n")]
+    #[case(
+        "print substr(\"gejang\", 4, 100); print substr(\"gejang\", 10, 20);",
+        "ng\n\n"
+    )]
+    #[case("print substr(\"gejang\", 4, 2);", "\n")]
+    #[case(
+        "print substr(\"안녕하세요\", 1, 3); print substr(\"a🦀b\", 1, 2);",
+        "녕하\n🦀\n"
+    )]
+    #[case("print split(\"a,b,c\", \",\");", "[a, b, c]\n")]
+    #[case("print split(\"a, b, c\", \", \");", "[a, b, c]\n")]
+    #[case("print split(\"abc\", \"\");", "[a, b, c]\n")]
+    #[case("print split(\"abc\", \";\");", "[abc]\n")]
+    #[case("print split(\",a,\", \",\");", "[, a, ]\n")]
+    #[case("print eval(\"1 + 2\");", "3\n")]
+    #[case("eval(\"var z = 5;\"); print z;", "5\n")]
+    #[case("print eval(\"var z = 5;\");", "nil\n")]
+    #[case(
+        "var g = 1; fun f() { var g = 2; return eval(\"g\"); } print f();",
+        "1\n"
+    )]
+    #[case("eval(\"fun twice(x) { return x * 2; }\"); print twice(4);", "8\n")]
+    #[case(
+        "var x = 0; for (var i = 0; i < 3; i = i + 1) eval(\"x = x + 1;\"); print x;",
+        "3\n"
+    )]
+    #[case(
+        "fun define() { eval(\"fun f() { return 1; }\"); } define(); define(); print f();",
+        "1\n"
+    )]
+    #[case(
+        "{ var a = 1; var b = 2; var c = 3; b = c + a; print a; print b; print c; }",
+        "1\n4\n3\n"
+    )]
+    #[case(
+        "{ var a = \"outer\"; { var b = \"x\"; var a = \"inner\"; print a; } print a; }",
+        "inner\nouter\n"
+    )]
+    #[case(
+        "{ fun fact(n) { if (n < 2) return 1; return n * fact(n - 1); } print fact(5); }",
+        "120\n"
+    )]
+    #[case(
+        "{ class A { make() { return A(); } } print A().make(); }",
+        "<instance of <cls A>>\n"
+    )]
+    #[case("{ if (false) fun f() {} var x = 1; print x; }", "1\n")]
+    #[case("{ if (true) print 0; else class C {} var x = 1; print x; }", "0\n1\n")]
+    #[case("{ while (false) fun f() {} var x = 1; print x; }", "1\n")]
+    #[case(
+        "{ fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); } fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); } print isEven(4); print isOdd(4); }",
+        "true\nfalse\n"
+    )]
+    #[case(
+        "fun outer() { fun a() { return b(); } var x = \"b\"; fun b() { return x; } return a; } print outer()();",
+        "b\n"
+    )]
+    #[case(
+        "fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); } fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); } print isEven(4);",
+        "true\n"
+    )]
+    #[case(
+        "var x = \"global\"; { fun f() { return x; } var x = \"local\"; print f(); }",
+        "global\n"
+    )]
+    #[case("var f = 1; fun f() { return 2; } print f;", "<fun f/0>\n")]
+    #[case(
+        "fun f() { return 1; } print f(); fun f() { return 2; } print f();",
+        "1\n2\n"
+    )]
+    #[case("{ var x = 1; fun get() { return x; } x = 2; print get(); }", "2\n")]
+    fn test_interpreter(#[case] source: &str, #[case] expected: &str) {
+        println!("source:\n{}", source);
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(source, &streams, Options::default());
+        println!("stdout:\n{}", streams.borrow().get_output().unwrap());
+        println!("stderr:\n{}", streams.borrow().get_error().unwrap());
+        r.unwrap();
+        assert_eq!(streams.borrow().get_output().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("chars(1);", "chars expects a string, got Number")]
+    #[case(
+        "fmtNumber(1, -1);",
+        "fmtNumber expects a non-negative whole number of decimals, got -1"
+    )]
+    #[case(
+        "fmtNumber(1, 101);",
+        "fmtNumber can show at most 100 decimals, got 101"
+    )]
+    #[case(
+        "fmtNumber(1, 1e18);",
+        "fmtNumber can show at most 100 decimals, got 1000000000000000000"
+    )]
+    #[case(
+        "fmtNumber(\"1\", 2);",
+        "fmtNumber expects two numbers, got String and Number"
+    )]
+    #[case(
+        "fun getCallback() { return nil; }\ngetCallback()\n();",
+        "line 3: Value nil is not callable: (getCallback)"
+    )]
+    #[case(
+        "class Obj { method() { return nil; } }\nvar obj = Obj();\nobj.method()();",
+        "line 3: Value nil is not callable: ((get obj method))"
+    )]
+    #[case(
+        "\"a\" < 1;",
+        "line 1: Binary operation not implemented: String < Number"
+    )]
+    #[case(
+        "var a = 1;\nvar b = \"b\";\nprint a >= b;",
+        "line 3: Binary operation not implemented: Number >= String"
+    )]
+    #[case("print\n-\"x\";", "line 2: Cannot negate non-number String")]
+    #[case("1.5 & 2;", "line 1: Operands of & must be whole numbers, got 1.5")]
+    #[case("1 ^ 0.5;", "line 1: Operands of ^ must be whole numbers, got 0.5")]
+    #[case(
+        "\"a\" | 1;",
+        "line 1: Binary operation not implemented: String | Number"
+    )]
+    #[case("range(-1);", "range expects non-negative whole numbers, got -1")]
+    #[case("range(1, 2.5);", "range expects non-negative whole numbers, got 2.5")]
+    #[case("range(\"3\");", "range expects numbers, got String")]
+    #[case("range(1, 2, 3);", "range expected 2 arguments but got 3")]
+    #[case(
+        "range(1e18);",
+        "range can make at most 1000000 numbers, got 1000000000000000000"
+    )]
+    #[case(
+        "range(5, 1000006);",
+        "range can make at most 1000000 numbers, got 1000001"
+    )]
+    #[case("undefined = 3;", "Undefined variable undefined")]
+    #[case("fun f() { y = 1; } f();", "Undefined variable y")]
+    #[case("var A = 1; class B < A {}", "Superclass must be a class, got 1")]
+    #[case(
+        "class A {} class B < A { m() { super.missing(); } } B().m();",
+        "Undefined variable missing"
+    )]
+    #[case("print 1[0:1];", "Only lists and strings can be sliced, got Number")]
+    #[case("print \"abc\"[0.5:1];", "Slice bounds must be whole numbers, got 0.5")]
+    #[case(
+        "startsWith(1, \"a\");",
+        "startsWith expects two strings, got Number and String"
+    )]
+    #[case(
+        "endsWith(\"a\", nil);",
+        "endsWith expects two strings, got String and Nil"
+    )]
+    #[case("trim(true);", "trim expects a string, got Boolean")]
+    #[case("len(12);", "len expects a string or a list, got Number")]
+    #[case(
+        "var xs = [1, 2];\nprint xs[2];",
+        "line 2: Index 2 is out of bounds for a list of length 2"
+    )]
+    #[case(
+        "var xs = [1, 2]; print xs[-3];",
+        "Index -3 is out of bounds for a list of length 2"
+    )]
+    #[case(
+        "var xs = [1, 2]; xs[5] = 1;",
+        "Index 5 is out of bounds for a list of length 2"
+    )]
+    #[case("print [][0];", "Index 0 is out of bounds for a list of length 0")]
+    #[case(
+        "print [1, 2][0.5];",
+        "line 1: List indices must be whole numbers, got 0.5"
+    )]
+    #[case(
+        "print \"abc\"[0];",
+        "line 1: Only lists and maps can be indexed, got String"
+    )]
+    #[case(
+        "var s = \"abc\"; s[0] = \"x\";",
+        "Only list and map elements can be assigned to, got String"
+    )]
+    #[case("push(1, 2);", "push expects a list, got Number")]
+    #[case("pop(\"abc\");", "pop expects a list, got String")]
+    #[case("pop([]);", "pop from an empty list")]
+    #[case(
+        "var m = {nil: 1};",
+        "line 1: Map keys must be strings or numbers, got Nil"
+    )]
+    #[case(
+        "var m = {};\nprint m[true];",
+        "line 2: Map keys must be strings or numbers, got Boolean"
+    )]
+    #[case(
+        "var m = {}; m[[]] = 1;",
+        "Map keys must be strings or numbers, got List"
+    )]
+    #[case("keys([1]);", "keys expects a map, got List")]
+    #[case("has(\"abc\", \"a\");", "has expects a map, got String")]
+    #[case("sqrt(\"4\");", "sqrt expects a number, got String")]
+    #[case("abs(nil);", "abs expects a number, got Nil")]
+    #[case("floor(true);", "floor expects a number, got Boolean")]
+    #[case("ceil(\"1.5\");", "ceil expects a number, got String")]
+    #[case("pow(2, \"3\");", "pow expects two numbers, got Number and String")]
+    #[case("num(\"abc\");", "num could not parse \"abc\" as a number")]
+    #[case("num(\"\");", "num could not parse \"\" as a number")]
+    #[case("num(nil);", "num expects a string or a number, got Nil")]
+    #[case("tsp2cup(\"x\");", "tsp2cup expects a number, got String")]
+    #[case(
+        "substr(\"abc\", -1, 2);",
+        "substr expects a string and two non-negative whole numbers, got String, Number, and Number"
+    )]
+    #[case(
+        "substr(\"abc\", 0, \"2\");",
+        "substr expects a string and two non-negative whole numbers, got String, Number, and String"
+    )]
+    #[case(
+        "substr(\"abc\", 0.5, 2);",
+        "substr expects a string and two non-negative whole numbers, got String, Number, and Number"
+    )]
+    #[case(
+        "substr(1, 0, 2);",
+        "substr expects a string and two non-negative whole numbers, got Number, Number, and Number"
+    )]
+    #[case("split(\"a\", 1);", "split expects two strings, got String and Number")]
+    #[case("eval(\"1 +\");", "eval failed: Unexpected end of input")]
+    #[case("eval(1);", "eval expects a string, got Number")]
+    #[case("exit(\"now\");", "exit expects a number, got String")]
+    #[case("assert();", "assert expected 1 argument but got 0")]
+    #[case("assert(true, \"a\", \"b\");", "assert expected 2 arguments but got 3")]
+    #[case("len(\"a\", \"b\");", "len expected 1 argument but got 2")]
+    #[case("clock(1);", "clock expected 0 arguments but got 1")]
+    #[case("fun count(x) {} count(1, 2);", "count expected 1 argument but got 2")]
+    #[case(
+        "fun add(a, b) { return a + b; } add(1);",
+        "add expected 2 arguments but got 1"
+    )]
+    #[case("class A { init(x) {} } A();", "init expected 1 argument but got 0")]
+    #[case(
+        "eval(\"while (true) {}\");",
+        "eval failed: ran for more than 1000000 steps"
+    )]
+    #[case(
+        "fun spin() { while (true) {} } eval(\"spin();\");",
+        "eval failed: ran for more than 1000000 steps"
+    )]
+    #[case(
+        "var s = \"eval(s);\"; eval(s);",
+        "eval failed: nested more than 64 evals deep"
+    )]
+    #[case("print early(); fun early() { return 1; }", "Undefined variable early")]
+    #[case(
+        "{ print early(); fun early() { return 1; } }",
+        "Undefined variable early"
+    )]
+    #[case(
+        "{ fun f() { return g(); } print f(); fun g() { return 1; } }",
+        "Undefined variable g"
+    )]
+    #[case("{ fun f() { g = 1; } f(); fun g() {} }", "Undefined variable g")]
+    #[case(
+        "for (x in 1) print x;",
+        "line 1: Can only loop over lists, got Number"
+    )]
+    fn test_runtime_errors(#[case] source: &str, #[case] expected: &str) {
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(source, &streams, Options::default());
+        assert_eq!(r, Err(InterpreterError::Evaluation));
+        assert!(streams.borrow().get_error().unwrap().contains(expected));
+    }
+
+    #[rstest]
+    #[case("exit(0);", 0, "")]
+    #[case("print 1; exit(3); print 2;", 3, "1\n")]
+    #[case("exit(-1.9);", -1, "")]
+    #[case("fun f() { while (true) { exit(2); } } f();", 2, "")]
+    fn test_exit(#[case] source: &str, #[case] code: i32, #[case] expected: &str) {
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(source, &streams, Options::default());
+        assert_eq!(r, Err(InterpreterError::Exit { code }));
+        assert_eq!(streams.borrow().get_output().unwrap(), expected);
+        assert_eq!(streams.borrow().get_error().unwrap(), "");
+    }
+
+    #[rstest]
+    #[case("", "print read_line();", "nil\n")]
+    #[case("hello\n", "print read_line();", "hello\n")]
+    #[case(
+        "no newline",
+        "print read_line(); print read_line();",
+        "no newline\nnil\n"
+    )]
+    #[case("crlf\r\n", "print len(read_line());", "4\n")]
+    #[case(
+        "one\n\nthree\n",
+        "var line = read_line(); while (line != nil) { print \"> \" + line; line = read_line(); }",
+        "> one\n> \n> three\n"
+    )]
+    fn test_read_line(#[case] input: &str, #[case] source: &str, #[case] expected: &str) {
+        let streams = RefCell::new(Streams {
+            input: input.as_bytes(),
+            ..Streams::test()
+        });
+        interpret(source, &streams, Options::default()).unwrap();
+        assert_eq!(streams.borrow().get_output().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("1 + 2;", Some("3"))]
+    #[case("var x = \"a\"; x + \"b\";", Some("ab"))]
+    #[case("fun f() {} f;", Some("<fun f/0>"))]
+    #[case("1 + 2; print 3;", None)]
+    #[case("var x = 1;", None)]
+    #[case("", None)]
+    fn test_interpret_returns_last_expression_value(
+        #[case] source: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(source, &streams, Options::default());
+
+        assert_eq!(r, Ok(expected.map(String::from)));
+    }
+
+    #[test]
+    fn test_reports_every_parse_error() {
+        let streams = RefCell::new(Streams::test());
+        let r = interpret("print 1\nprint 2;\nvar = 3;", &streams, Options::default());
+
+        assert_eq!(r, Err(InterpreterError::Parser));
+        assert_eq!(streams.borrow().get_output().unwrap(), "");
+        let error = streams.borrow().get_error().unwrap();
+        assert!(error.contains("Expected ; at the end of the statement on line 1"));
+        assert!(error.contains("on line 3, column 5, but got ="));
+    }
+
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn test_trace(#[case] trace: bool) {
+        let streams = RefCell::new(Streams::test());
+        let options = Options {
+            trace,
+            ..Options::default()
+        };
+        interpret("var x = 1; { var y = 2; print x + y; }", &streams, options).unwrap();
+
+        assert_eq!(streams.borrow().get_output().unwrap(), "3\n");
+        let error = streams.borrow().get_error().unwrap();
+        if trace {
+            assert_eq!(error, "Looking up x at global\nLooking up y at depth 0\n");
+        } else {
+            assert_eq!(error, "");
+        }
+    }
+
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    fn test_warnings(#[case] warnings: bool) {
+        let streams = RefCell::new(Streams::test());
+        let options = Options {
+            warnings,
+            ..Options::default()
+        };
+        let r = interpret("var x; if (x = 1) print x;", &streams, options);
+        r.unwrap();
+        assert_eq!(streams.borrow().get_output().unwrap(), "1\n");
+        assert_eq!(
+            streams
+                .borrow()
+                .get_error()
+                .unwrap()
+                .contains("line 1: assignment to x used as a condition"),
+            warnings
+        );
+    }
+
+    #[test]
+    fn test_repl_recovers_scopes_after_error() {
+        let streams = RefCell::new(Streams::test());
+        let eval_arena = EvalArena::default();
+        let session = Session::new(&streams, &eval_arena, Options::default()).unwrap();
+
+        session.run("var x = \"global\";").unwrap();
+        assert_eq!(
+            session.run("{ var x = \"local\"; nope(); }"),
+            Err(InterpreterError::Evaluation)
+        );
+        session.run("print x;").unwrap();
+        session.run("{ var y = 1; print y; }").unwrap();
+        session.run("var z = 2;").unwrap();
+        session.run("print z;").unwrap();
+
+        assert_eq!(streams.borrow().get_output().unwrap(), "global\n1\n2\n");
+    }
+
+    /// Run the REPL over the given input, reading it line by line, and return its streams.
+    fn run_repl_on(input: &str) -> Streams<&[u8], Vec<u8>, Vec<u8>> {
+        let streams = RefCell::new(Streams {
+            input: input.as_bytes(),
+            ..Streams::test()
+        });
+        run_repl(
+            &streams,
+            &mut StreamLineReader::new(&streams),
+            Options::default(),
+        )
+        .unwrap();
+
+        streams.into_inner()
+    }
+
+    #[rstest]
+    #[case("3 * 4\n", "12\n")]
+    #[case("3 * 4;\n", "12\n")]
+    #[case("var x = 5;\nx\n", "5\n")]
+    #[case("print 1; 2;\n", "1\n")]
+    #[case("\"hi\"\n", "hi\n")]
+    #[case("nil\n", "")]
+    #[case("fun f() {}\nf()\n", "")]
+    fn test_repl_echoes_bare_expressions(#[case] input: &str, #[case] expected: &str) {
+        let streams = run_repl_on(input);
+
+        let output = streams.get_output().unwrap();
+        let echoed = output.replace("🦀> ", "");
+        assert_eq!(
+            echoed.trim_end_matches('\n'),
+            expected.trim_end_matches('\n')
+        );
+    }
+
+    #[rstest]
+    #[case("fun f() {", true)]
+    #[case("fun f() {\nprint 1;", true)]
+    #[case("fun f() {\nprint 1;}", false)]
+    #[case("print (1 +", true)]
+    #[case("var x =", true)]
+    #[case("fun", true)]
+    #[case("print 1", false)]
+    #[case("print 1;", false)]
+    #[case("print \"unterminated", false)]
+    #[case("", false)]
+    fn test_is_unfinished(#[case] source: &str, #[case] expected: bool) {
+        assert_eq!(is_unfinished(source), expected);
+    }
+
+    #[rstest]
+    #[case("fun f(){\nprint 1;}\nf();\n", "1\n")]
+    #[case("{\nvar x = 1;\nprint x;\n}\n", "1\n")]
+    #[case("(1 +\n2)\n", "3\n")]
+    fn test_repl_reads_unfinished_statements_across_lines(
+        #[case] input: &str,
+        #[case] expected: &str,
+    ) {
+        let streams = run_repl_on(input);
+
+        let output = streams.get_output().unwrap();
+        assert!(output.ends_with(&format!("{expected}🦀> \n")), "{output:?}");
+        assert_eq!(streams.get_error().unwrap(), "");
+    }
+
+    #[test]
+    fn test_repl_reads_piped_input_line_by_line() {
+        let streams = run_repl_on("var x = 1;\nx + 1;\nfun f() {\n  return x;\n}\nf();\n");
+
+        assert_eq!(
+            streams.get_output().unwrap(),
+            "🦀> 🦀> 2\n🦀> ... ... 🦀> 1\n🦀> \n"
+        );
+        assert_eq!(streams.get_error().unwrap(), "");
+    }
+
+    #[test]
+    fn test_repl_help_and_quit() {
+        let streams = run_repl_on(":help\n:quit\nprint 1;\n");
+
+        let output = streams.get_output().unwrap();
+        assert_eq!(output, format!("🦀> {REPL_HELP}\n🦀> "));
+        assert_eq!(streams.get_error().unwrap(), "");
+    }
+
+    #[rstest]
+    #[case(":ast 1 + 2 * 3\n", "(+ 1 (* 2 3))\n")]
+    #[case(":ast print x;\n", "(print x)\n")]
+    #[case("var x = 1;\n:ast x = 2\nx;\n", "(assign x 2)\n1\n")]
+    #[case("var x = 1;\n:clear\nvar x = 2;\nx;\n", "2\n")]
+    #[case("eval(\"var x = 1;\");\n:clear\neval(\"print 2;\");\n", "2\n")]
+    fn test_repl_commands(#[case] input: &str, #[case] expected: &str) {
+        let streams = run_repl_on(input);
+
+        let output = streams.get_output().unwrap();
+        let echoed = output.replace("🦀> ", "").replace("... ", "");
+        assert_eq!(
+            echoed.trim_end_matches('\n'),
+            expected.trim_end_matches('\n')
+        );
+        assert_eq!(streams.get_error().unwrap(), "");
+    }
+
+    #[test]
+    fn test_repl_clear_forgets_globals() {
+        let streams = run_repl_on("var x = 1;\n:clear\nprint x;\n");
+
+        assert!(streams
+            .get_error()
+            .unwrap()
+            .contains("Undefined variable x"));
+    }
+
+    #[test]
+    fn test_repl_unknown_command() {
+        let streams = run_repl_on(":nope\n");
+
+        assert!(streams
+            .get_error()
+            .unwrap()
+            .contains("Unknown command :nope"));
+        assert!(streams.get_output().unwrap().contains("😵> "));
+    }
+
+    #[test]
+    fn test_repl_survives_unfinished_function_name() {
+        let streams = run_repl_on("fun\n\nprint 2;\n");
+
+        assert!(streams
+            .get_error()
+            .unwrap()
+            .contains("Unexpected end of input"));
+        assert!(streams.get_output().unwrap().contains("😵> 2\n"));
+    }
+
+    #[test]
+    fn test_repl_blank_line_abandons_unfinished_statement() {
+        let streams = run_repl_on("fun f() {\n\nprint 2;\n");
+
+        assert!(streams
+            .get_error()
+            .unwrap()
+            .contains("Unexpected end of input"));
+        assert!(streams.get_output().unwrap().contains("😵> 2\n"));
+    }
+
+    #[test]
+    fn test_features() {
+        let streams = RefCell::new(Streams::test());
+        write_features(&streams).unwrap();
+
+        let output = streams.borrow().get_output().unwrap();
+        let listed = output.lines().collect_vec();
+        assert!(listed.contains(&"closures"));
+        assert!(listed.contains(&"classes"));
+        assert_eq!(listed.contains(&"debug-assertions"), cfg!(debug_assertions));
+    }
+
+    #[test]
+    fn test_with_output() {
+        let path = std::env::temp_dir().join("gejang_test_with_output.txt");
+        let source = format!(
+            r#"
+fun report() {{
+  print "inside";
+}}
+
+print "before";
+withOutput("{}", report);
+print "after";"#,
+            path.display()
+        );
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(&source, &streams, Options::default());
+        println!("stderr:\n{}", streams.borrow().get_error().unwrap());
+        r.unwrap();
+        assert_eq!(streams.borrow().get_output().unwrap(), "before\nafter\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "inside\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[rstest]
+    #[case("assert(true); print \"ok\";", Ok(()))]
+    #[case("assert(1 < 2, \"math works\"); print \"ok\";", Ok(()))]
+    #[case("assert(0); assert(\"\"); assert([]); print \"ok\";", Ok(()))]
+    #[case(
+        "assert(false);",
+        Err("line 1: Assertion failed: expected a truthy value, got false")
+    )]
+    #[case(
+        "var x;\nassert(x, \"x should be set\");",
+        Err("line 2: Assertion failed: x should be set")
+    )]
+    #[case("assert(nil, 42);", Err("line 1: Assertion failed: 42"))]
+    fn test_assert(#[case] source: &str, #[case] expected: Result<(), &str>) {
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(source, &streams, Options::default());
+
+        match expected {
+            Ok(()) => {
+                assert_eq!(r, Ok(None));
+                assert_eq!(streams.borrow().get_output().unwrap(), "ok\n");
+            }
+            Err(msg) => {
+                assert_eq!(
+                    r,
+                    Err(InterpreterError::AssertionFailed {
+                        msg: msg.to_string()
+                    })
+                );
+                assert_eq!(streams.borrow().get_output().unwrap(), "");
+            }
+        }
+    }
+
+    #[test]
+    fn test_harness_reports_assertion_failures() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let scripts = vec![
+            fixtures.join("failing_assert.🦀"),
+            fixtures.join("passing_assert.🦀"),
+        ];
+        let streams = RefCell::new(Streams::test());
+
+        let failures = run_tests(&scripts, &streams, Options::default()).unwrap();
+
+        let output = streams.borrow().get_output().unwrap();
+        println!("stdout:\n{}", output);
+        assert_eq!(failures, 1);
+        assert!(output.lines().any(|l| l.contains("FAIL")
+            && l.contains(&format!(
+                "{}: line 3: Assertion failed: expected a truthy value, got false",
+                scripts[0].display()
+            ))));
+        assert!(output
+            .lines()
+            .any(|l| l.contains("PASS") && l.contains(&scripts[1].display().to_string())));
+        assert!(streams.borrow().get_error().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_harness_reports_unreadable_scripts_and_keeps_going() {
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let scripts = vec![
+            fixtures.join("passing_assert.🦀"),
+            fixtures.join("missing.🦀"),
+            fixtures.join("failing_assert.🦀"),
+        ];
+        let streams = RefCell::new(Streams::test());
+
+        let failures = run_tests(&scripts, &streams, Options::default()).unwrap();
+
+        let output = streams.borrow().get_output().unwrap();
+        let lines = output.lines().collect_vec();
+        assert_eq!(failures, 2);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("PASS"));
+        assert!(
+            lines[1].contains("FAIL") && lines[1].contains(&format!("{}: ", scripts[1].display()))
+        );
+        assert!(lines[2].contains("FAIL") && lines[2].contains("Assertion failed"));
+    }
+
+    #[rstest]
+    #[case("1 + 2", "1: number(1) \"1\"\n1: + \"+\"\n1: number(2) \"2\"\n")]
+    #[case(
+        "var x; // note\nprint x;",
+        "1: var \"var\"\n1: identifier(x) \"x\"\n1: ; \";\"\n1: a comment \"// note\"\n2: print \"print\"\n2: identifier(x) \"x\"\n2: ; \";\"\n"
+    )]
+    #[case("f(a, b)", "1: identifier(f) \"f\"\n1: ( \"(\"\n1: identifier(a) \"a\"\n1: , \",\"\n1: identifier(b) \"b\"\n1: ) \")\"\n")]
+    #[case("", "")]
+    fn test_dump_tokens(#[case] source: &str, #[case] expected: &str) {
+        let streams = RefCell::new(Streams::test());
+        dump_tokens(source, &streams).unwrap();
+        assert_eq!(streams.borrow().get_output().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_dump_tokens_stops_at_first_error() {
+        let streams = RefCell::new(Streams::test());
+        let r = dump_tokens("1 @ 2 #", &streams);
+
+        assert_eq!(r, Err(InterpreterError::Scanner));
+        assert_eq!(
+            streams.borrow().get_output().unwrap(),
+            "1: number(1) \"1\"\n"
+        );
+        assert_eq!(streams.borrow().get_error().unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_dump_ast() {
+        let source = r#"
+var x = 1;
+fun add(a, b) {
+  return a + b;
+}
+if (x > 0) print add(x, 2); else print "no";"#;
+        let streams = RefCell::new(Streams::test());
+        dump_ast(source, &streams, Options::default(), false).unwrap();
+        assert_eq!(
+            streams.borrow().get_output().unwrap(),
+            "\
+(var x 1)
+(function add (a b) ((return (+ a b))))
+(if (> x 0) then (print (add x, 2)) else (print \"no\"))
+"
+        );
+    }
+
+    #[test]
+    fn test_dump_ast_as_json() {
+        let streams = RefCell::new(Streams::test());
+        dump_ast("x;", &streams, Options::default(), true).unwrap();
+        assert_eq!(
+            streams.borrow().get_output().unwrap(),
+            "[{\"type\":\"Expression\",\"expr\":{\"type\":\"Variable\",\"name\":{\"lexeme\":\"x\",\"line\":1,\"column\":1}}}]\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_ast_reports_errors_without_output() {
+        let streams = RefCell::new(Streams::test());
+        let r = dump_ast("print 1;\nvar = 2;", &streams, Options::default(), false);
+
+        assert_eq!(r, Err(InterpreterError::Parser));
+        assert_eq!(streams.borrow().get_output().unwrap(), "");
+        assert!(streams
+            .borrow()
+            .get_error()
+            .unwrap()
+            .contains("on line 2, column 5, but got ="));
+    }
+
+    #[test]
+    fn test_resolved_depths() {
+        let source = r#"
+fun makeCounter() {
+  var i = 0;
+  fun count() {
+    i = i + 1;
+    print i;
+  }
+
+  return count;
+}
+
+var counter = makeCounter();
+counter();"#;
+        let streams = RefCell::new(Streams::test());
+        resolved_depths(source, &streams).unwrap();
+        assert_eq!(
+            streams.borrow().get_output().unwrap(),
+            "\
+5: i -> 0
+5: (assign i (+ i 1)) -> 0
+6: i -> 0
+9: count -> 0
+12: makeCounter -> global
+13: counter -> global
+"
+        );
+    }
+
+    #[test]
+    fn test_clock() {
+        let source = "print clock();";
+        println!("source:\n{}", source);
+        let streams = RefCell::new(Streams::test());
+        let r = interpret(source, &streams, Options::default());
+        println!("stdout:\n{}", streams.borrow().get_output().unwrap());
+        println!("stderr:\n{}", streams.borrow().get_error().unwrap());
+        r.unwrap();
         // TODO: real assert here, for now just making sure it runs without errors
         // assert!(streams.borrow().get_output().unwrap(), expected);
     }
+
+    #[bench]
+    fn bench_long_expressions(b: &mut Bencher) {
+        let source = format!(
+            "var x = 0; for (var i = 0; i < 100; i = i + 1) {{ x = {}; }}",
+            (0..50).map(|_| "i").join(" + ")
+        );
+
+        b.iter(|| interpret(&source, &RefCell::new(Streams::test()), Options::default()).unwrap());
+    }
+
+    #[bench]
+    fn bench_parse_large_file(b: &mut Bencher) {
+        let source = large_file();
+        let streams = RefCell::new(Streams::test());
+
+        b.iter(|| {
+            let tokens = Arena::new();
+            scan_and_parse(&source, &tokens, &streams, Options::default())
+                .unwrap()
+                .len()
+        });
+    }
+
+    #[bench]
+    fn bench_comparisons(b: &mut Bencher) {
+        let source = "var n = 0; for (var i = 0; i < 1000; i = i + 1) { if (i >= 500 == !(i < 500)) n = n + 1; }";
+
+        b.iter(|| interpret(source, &RefCell::new(Streams::test()), Options::default()).unwrap());
+    }
+
+    #[bench]
+    fn bench_recursive_calls(b: &mut Bencher) {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } fib(15);";
+
+        b.iter(|| interpret(source, &RefCell::new(Streams::test()), Options::default()).unwrap());
+    }
+
+    #[bench]
+    fn bench_local_variable_reads(b: &mut Bencher) {
+        let source = "fun f() { var a = 1; var b = 2; var c = 3; var n = 0; for (var i = 0; i < 1000; i = i + 1) { n = a + b + c + n; } return n; } f();";
+
+        b.iter(|| interpret(source, &RefCell::new(Streams::test()), Options::default()).unwrap());
+    }
+
+    #[test]
+    fn test_string_concatenation_allocates_like_arithmetic() {
+        // The strings are built over and over, but only allocated the first time,
+        // so each extra iteration allocates no more than adding numbers does.
+        let per_iteration = |body: &str| {
+            let run = |n: usize| {
+                allocations(&format!(
+                    "var s; for (var i = 0; i < {n}; i = i + 1) {{ s = {body}; }}"
+                ))
+            };
+            (run(1000) - run(100)) / 900
+        };
+
+        assert_eq!(
+            per_iteration("\"key\" + \"-\" + \"value\""),
+            per_iteration("1 + 2 + 3")
+        );
+    }
+
+    #[bench]
+    fn bench_string_concatenation(b: &mut Bencher) {
+        // The same few strings are built over and over, so they're only allocated the first time.
+        let source =
+            "var s; for (var i = 0; i < 1000; i = i + 1) { s = \"key\" + \"-\" + \"value\"; }";
+
+        b.iter(|| interpret(source, &RefCell::new(Streams::test()), Options::default()).unwrap());
+    }
 }
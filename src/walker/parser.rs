@@ -1,4 +1,9 @@
-use std::iter::Peekable;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    iter::Peekable,
+    rc::Rc,
+};
 
 use thiserror::Error;
 
@@ -9,13 +14,22 @@ use crate::{
 
 #[derive(Error, Clone, PartialEq, PartialOrd, Debug)]
 pub enum ParserError<'s> {
-    #[error("Expected {expected} on line {}, but got {}", .token.line, .token.typ)]
+    #[error("Expected {expected} on line {}, column {}, but got {}", .token.line, .token.column, .token.typ)]
     UnexpectedToken {
         expected: TokenType<'s>,
         token: &'s Token<'s>,
     },
     #[error("Unexpected end of input")]
     UnexpectedEndOfInput,
+    #[error("Expected ; at the end of the statement on line {after_line}")]
+    MissingSemicolon { after_line: usize },
+    #[error("Expected , before {} on line {}, column {}", .token.typ, .token.line, .token.column)]
+    MissingComma { token: &'s Token<'s> },
+    #[error("Expected {{ to start the body of {keyword} on line {}, column {}, but got {}", .token.line, .token.column, .token.typ)]
+    UnbracedBody {
+        keyword: &'static str,
+        token: &'s Token<'s>,
+    },
     #[error("Invalid assignment target")] // better debug info
     InvalidAssignmentTarget,
     #[error("{msg}")]
@@ -23,7 +37,7 @@ pub enum ParserError<'s> {
 }
 
 type ParserExprResult<'s> = Result<Expr<'s>, ParserError<'s>>;
-type ParserStmtResult<'s> = Result<Stmt<'s>, ParserError<'s>>;
+pub(crate) type ParserStmtResult<'s> = Result<Stmt<'s>, ParserError<'s>>;
 
 /*
 Trying to write this the way the book wants doesn't work
@@ -53,20 +67,72 @@ Parser's `&mut self` lifetime instead of
 the data inside the iterator.
 */
 
-struct Parser<I>
-where
-    I: Iterator,
-{
-    tokens: Peekable<I>,
+type Docs<'s> = Rc<RefCell<HashMap<*const Token<'s>, String>>>;
+/// The last two tokens handed out, oldest first.
+type Recent<'s> = Rc<Cell<[Option<&'s Token<'s>>; 2]>>;
+
+/// Pulls tokens on demand, skipping comments. A run of `///` comments is
+/// remembered against the token that follows it so declarations can pick it up.
+struct Tokens<'s> {
+    inner: Box<dyn Iterator<Item = &'s Token<'s>> + 's>,
+    docs: Docs<'s>,
+    recent: Recent<'s>,
 }
 
-impl<'s, I> From<I> for Parser<I>
+impl<'s> Iterator for Tokens<'s> {
+    type Item = &'s Token<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut pending: Vec<&str> = Vec::new();
+
+        for token in self.inner.by_ref() {
+            if let TokenType::Comment(text) = token.typ {
+                match text.strip_prefix("///") {
+                    Some(line) => pending.push(line.trim()),
+                    None => pending.clear(),
+                }
+            } else {
+                if !pending.is_empty() {
+                    self.docs
+                        .borrow_mut()
+                        .insert(token as *const Token, pending.join("\n"));
+                }
+                let [_, last] = self.recent.get();
+                self.recent.set([last, Some(token)]);
+                return Some(token);
+            }
+        }
+
+        None
+    }
+}
+
+struct Parser<'s> {
+    tokens: Peekable<Tokens<'s>>,
+    docs: Docs<'s>,
+    recent: Recent<'s>,
+    /// Require the bodies of `if`, `else`, `while`, and `for` to be blocks.
+    strict_braces: bool,
+}
+
+impl<'s, I> From<I> for Parser<'s>
 where
-    I: Iterator<Item = &'s Token<'s>>,
+    I: Iterator<Item = &'s Token<'s>> + 's,
 {
     fn from(tokens: I) -> Self {
+        let docs = Docs::default();
+        let recent = Recent::default();
+
         Parser {
-            tokens: tokens.peekable(),
+            tokens: Tokens {
+                inner: Box::new(tokens),
+                docs: Rc::clone(&docs),
+                recent: Rc::clone(&recent),
+            }
+            .peekable(),
+            docs,
+            recent,
+            strict_braces: false,
         }
     }
 }
@@ -75,39 +141,47 @@ const TRUE: Token = Token {
     typ: TokenType::True,
     lexeme: "true",
     line: 0,
+    column: 0,
 };
 
-impl<'s, I> Parser<I>
-where
-    I: Iterator<Item = &'s Token<'s>>,
-{
-    #[allow(dead_code)]
+impl<'s> Parser<'s> {
+    /// After an error, skip ahead to where the next statement probably starts,
+    /// so that one pass can report every independent error.
+    ///
+    /// The token the error was found at is only skipped if it can't start a statement,
+    /// since a missing `;` is usually noticed at the start of the next statement.
+    /// That can't loop forever: a declaration always consumes its leading keyword,
+    /// so an error is never found at the same keyword twice.
     fn synchronize(&mut self) {
-        while let Some(token) = self.tokens.next() {
-            // If we are at the end of the current statement...
-            if matches!(token.typ, TokenType::Semicolon) {
+        while let Some(next) = self.tokens.peek() {
+            // If we are at the beginning of a new statement...
+            if matches!(
+                next.typ,
+                TokenType::Class
+                    | TokenType::Fun
+                    | TokenType::Var
+                    | TokenType::For
+                    | TokenType::If
+                    | TokenType::While
+                    | TokenType::Switch
+                    | TokenType::Print
+                    | TokenType::Return
+            ) {
                 return;
             }
 
-            // ... or at the beginning of a new statement
-            if let Some(next) = self.tokens.peek() {
-                if matches!(
-                    next.typ,
-                    TokenType::Class
-                        | TokenType::Fun
-                        | TokenType::Var
-                        | TokenType::For
-                        | TokenType::If
-                        | TokenType::While
-                        | TokenType::Print
-                        | TokenType::Return
-                ) {
-                    return;
-                }
+            // ... or just past the end of the current one.
+            if let Some(TokenType::Semicolon) = self.tokens.next().map(|t| &t.typ) {
+                return;
             }
         }
     }
 
+    /// The `///` comment text that immediately preceded this token, if any.
+    fn doc(&mut self, token: &'s Token<'s>) -> Option<String> {
+        self.docs.borrow_mut().remove(&(token as *const Token))
+    }
+
     fn parse(&mut self) -> Vec<ParserStmtResult<'s>> {
         let mut statements = Vec::new();
         while self.tokens.peek().is_some() {
@@ -144,7 +218,7 @@ where
                 None
             };
 
-            self.require_token(TokenType::Semicolon)?;
+            self.require_semicolon()?;
 
             Ok(Stmt::Var { name, initializer })
         } else {
@@ -173,7 +247,9 @@ where
                     | TokenType::Fun
                     | TokenType::Return
                     | TokenType::Break
+                    | TokenType::Continue
                     | TokenType::Class
+                    | TokenType::Switch
             )
         }) {
             match token.typ {
@@ -182,13 +258,24 @@ where
                 TokenType::LeftBrace => self.block(),
                 TokenType::If => self.if_statement(),
                 TokenType::While => self.while_statement(),
-                TokenType::Fun => self.function(),
-                TokenType::Return => self.return_statement(),
+                TokenType::Fun => {
+                    let doc = self.doc(token);
+                    self.function(doc, false)
+                }
+                TokenType::Return => self.return_statement(token),
                 TokenType::Break => {
-                    self.require_token(TokenType::Semicolon)?;
-                    Ok(Stmt::Break)
+                    self.require_semicolon()?;
+                    Ok(Stmt::Break { keyword: token })
+                }
+                TokenType::Continue => {
+                    self.require_semicolon()?;
+                    Ok(Stmt::Continue { keyword: token })
                 }
-                TokenType::Class => self.class_declaration(),
+                TokenType::Class => {
+                    let doc = self.doc(token);
+                    self.class_declaration(doc)
+                }
+                TokenType::Switch => self.switch_statement(),
                 _ => unreachable!("Unimplemented statement type"),
             }
         } else {
@@ -212,7 +299,23 @@ where
         {
             Some(self.variable_declaration()?)
         } else {
-            Some(self.expression_statement()?)
+            let expr = self.expression()?;
+
+            // `for (name in iterable)` loops over the items of a list instead.
+            if let Expr::Variable { name } = &expr {
+                if self
+                    .tokens
+                    .next_if(|t| matches!(t.typ, TokenType::In))
+                    .is_some()
+                {
+                    return self.for_in_statement(name);
+                }
+            }
+
+            self.require_semicolon()?;
+            Some(Stmt::Expression {
+                expr: Box::new(expr),
+            })
         };
 
         let condition = if self
@@ -239,17 +342,10 @@ where
 
         self.require_token(TokenType::RightParen)?;
 
-        let mut body = self.statement()?;
-
-        if let Some(i) = increment {
-            body = Stmt::Block {
-                stmts: vec![body, Stmt::Expression { expr: Box::new(i) }],
-            }
-        }
-
-        body = Stmt::While {
+        let mut body = Stmt::While {
             condition: Box::new(condition),
-            body: Box::new(body),
+            body: Box::new(self.body("for")?),
+            increment: increment.map(Box::new),
         };
 
         if let Some(i) = initializer {
@@ -261,14 +357,56 @@ where
         Ok(body)
     }
 
-    fn print_statement(&mut self) -> ParserStmtResult<'s> {
-        let expr = self.expression()?;
-        self.require_token(TokenType::Semicolon)?;
-        Ok(Stmt::Print {
-            expr: Box::new(expr),
+    fn for_in_statement(&mut self, name: &'s Token<'s>) -> ParserStmtResult<'s> {
+        let iterable = self.expression()?;
+
+        self.require_token(TokenType::RightParen)?;
+
+        Ok(Stmt::ForIn {
+            name,
+            iterable: Box::new(iterable),
+            body: Box::new(self.body("for")?),
         })
     }
 
+    /// The body of a control flow statement, which must be a block in strict-braces mode.
+    fn body(&mut self, keyword: &'static str) -> ParserStmtResult<'s> {
+        if self.strict_braces {
+            if let Some(token) = self
+                .tokens
+                .peek()
+                .filter(|t| !matches!(t.typ, TokenType::LeftBrace))
+            {
+                return Err(ParserError::UnbracedBody { keyword, token });
+            }
+        }
+
+        self.statement()
+    }
+
+    fn print_statement(&mut self) -> ParserStmtResult<'s> {
+        let mut exprs = vec![];
+
+        // A bare `print;` prints an empty line.
+        if self
+            .tokens
+            .peek()
+            .is_some_and(|t| !matches!(t.typ, TokenType::Semicolon))
+        {
+            exprs.push(self.expression()?);
+            while self
+                .tokens
+                .next_if(|t| matches!(t.typ, TokenType::Comma))
+                .is_some()
+            {
+                exprs.push(self.expression()?);
+            }
+        }
+
+        self.require_semicolon()?;
+        Ok(Stmt::Print { exprs })
+    }
+
     fn block(&mut self) -> ParserStmtResult<'s> {
         let mut stmts = Vec::new();
 
@@ -289,13 +427,22 @@ where
         self.require_token(TokenType::LeftParen)?;
         let condition = Box::new(self.expression()?);
         self.require_token(TokenType::RightParen)?;
-        let then = Box::new(self.statement()?);
+        let then = Box::new(self.body("if")?);
         let els = if self
             .tokens
             .next_if(|t| matches!(t.typ, TokenType::Else))
             .is_some()
         {
-            Some(Box::new(self.statement()?))
+            // `else if` chains are fine, since the inner `if` will check its own body.
+            if self
+                .tokens
+                .peek()
+                .is_some_and(|t| matches!(t.typ, TokenType::If))
+            {
+                Some(Box::new(self.statement()?))
+            } else {
+                Some(Box::new(self.body("else")?))
+            }
         } else {
             None
         };
@@ -307,59 +454,130 @@ where
         })
     }
 
+    fn switch_statement(&mut self) -> ParserStmtResult<'s> {
+        self.require_token(TokenType::LeftParen)?;
+        let subject = Box::new(self.expression()?);
+        self.require_token(TokenType::RightParen)?;
+        self.require_token(TokenType::LeftBrace)?;
+
+        let mut cases = vec![];
+        let mut default = None;
+
+        while let Some(token) = self
+            .tokens
+            .next_if(|t| matches!(t.typ, TokenType::Case | TokenType::Default))
+        {
+            let value = if matches!(token.typ, TokenType::Case) {
+                Some(self.expression()?)
+            } else if default.is_some() {
+                return Err(ParserError::Error {
+                    msg: format!("Duplicate default case on line {}", token.line),
+                });
+            } else {
+                None
+            };
+            self.require_token(TokenType::Colon)?;
+
+            // A case's body runs until the next case, or the end of the switch.
+            let mut body = vec![];
+            while self.tokens.peek().is_some_and(|t| {
+                !matches!(
+                    t.typ,
+                    TokenType::Case | TokenType::Default | TokenType::RightBrace
+                )
+            }) {
+                body.push(self.declaration()?);
+            }
+
+            match value {
+                Some(v) => cases.push((v, body)),
+                None => default = Some(body),
+            }
+        }
+
+        self.require_token(TokenType::RightBrace)?;
+
+        Ok(Stmt::Switch {
+            subject,
+            cases,
+            default,
+        })
+    }
+
     fn while_statement(&mut self) -> ParserStmtResult<'s> {
         self.require_token(TokenType::LeftParen)?;
         let condition = Box::new(self.expression()?);
         self.require_token(TokenType::RightParen)?;
-        let body = Box::new(self.statement()?);
+        let body = Box::new(self.body("while")?);
 
-        Ok(Stmt::While { condition, body })
+        Ok(Stmt::While {
+            condition,
+            body,
+            increment: None,
+        })
     }
 
-    fn function(&mut self) -> ParserStmtResult<'s> {
+    /// A function declaration, or a method if `method` is set,
+    /// in which case leaving off the parameter list makes it a getter.
+    fn function(&mut self, doc: Option<String>, method: bool) -> ParserStmtResult<'s> {
         let name = self
             .tokens
             .next_if(|t| matches!(t.typ, TokenType::Identifier(_)))
-            .ok_or_else(|| ParserError::UnexpectedToken {
-                expected: TokenType::Identifier(""),
-                token: self
-                    .tokens
+            .ok_or_else(|| {
+                self.tokens
                     .peek()
-                    .ok_or(ParserError::UnexpectedEndOfInput)
-                    .unwrap(), // TODO what?
+                    .map_or(ParserError::UnexpectedEndOfInput, |token| {
+                        ParserError::UnexpectedToken {
+                            expected: TokenType::Identifier(""),
+                            token,
+                        }
+                    })
             })?;
 
-        self.require_token(TokenType::LeftParen)?;
+        let getter = method
+            && self
+                .tokens
+                .peek()
+                .is_some_and(|t| matches!(t.typ, TokenType::LeftBrace));
+
+        if !getter {
+            self.require_token(TokenType::LeftParen)?;
+        }
 
         let mut params = vec![];
 
-        while self
-            .tokens
-            .peek()
-            .is_some_and(|t| !matches!(t.typ, TokenType::RightParen))
+        while !getter
+            && self
+                .tokens
+                .peek()
+                .is_some_and(|t| !matches!(t.typ, TokenType::RightParen))
         {
             params.push(
                 self.tokens
                     .next_if(|t| matches!(t.typ, TokenType::Identifier(_)))
-                    .ok_or_else(|| ParserError::UnexpectedToken {
-                        expected: TokenType::Identifier(""),
-                        token: self
-                            .tokens
+                    .ok_or_else(|| {
+                        self.tokens
                             .peek()
-                            .ok_or(ParserError::UnexpectedEndOfInput)
-                            .unwrap(), // TODO what?
+                            .map_or(ParserError::UnexpectedEndOfInput, |token| {
+                                ParserError::UnexpectedToken {
+                                    expected: TokenType::Identifier(""),
+                                    token,
+                                }
+                            })
                     })?,
             );
-            if !self
+            if self
                 .tokens
-                .peek()
-                .is_some_and(|t| matches!(t.typ, TokenType::Comma))
+                .next_if(|t| matches!(t.typ, TokenType::Comma))
+                .is_none()
             {
                 break;
             }
         }
 
-        self.require_token(TokenType::RightParen)?;
+        if !getter {
+            self.require_token(TokenType::RightParen)?;
+        }
 
         self.require_token(TokenType::LeftBrace)?;
 
@@ -374,10 +592,16 @@ where
 
         self.require_token(TokenType::RightBrace)?;
 
-        Ok(Stmt::Function { name, params, body })
+        Ok(Stmt::Function {
+            name,
+            params,
+            body,
+            doc,
+            getter,
+        })
     }
 
-    fn return_statement(&mut self) -> ParserStmtResult<'s> {
+    fn return_statement(&mut self, keyword: &'s Token<'s>) -> ParserStmtResult<'s> {
         let value = if self
             .tokens
             .peek()
@@ -388,16 +612,35 @@ where
             None
         };
 
-        self.require_token(TokenType::Semicolon)?;
+        self.require_semicolon()?;
 
-        Ok(Stmt::Return { value })
+        Ok(Stmt::Return { keyword, value })
     }
 
-    fn class_declaration(&mut self) -> ParserStmtResult<'s> {
+    fn class_declaration(&mut self, doc: Option<String>) -> ParserStmtResult<'s> {
         if let Some(name) = self
             .tokens
             .next_if(|t| matches!(t.typ, TokenType::Identifier(_)))
         {
+            let superclass = if self
+                .tokens
+                .next_if(|t| matches!(t.typ, TokenType::Less))
+                .is_some()
+            {
+                if let Some(name) = self
+                    .tokens
+                    .next_if(|t| matches!(t.typ, TokenType::Identifier(_)))
+                {
+                    Some(Box::new(Expr::Variable { name }))
+                } else {
+                    return Err(ParserError::Error {
+                        msg: "Expected superclass name after <".into(),
+                    });
+                }
+            } else {
+                None
+            };
+
             self.require_token(TokenType::LeftBrace)?;
 
             let mut methods = Vec::new();
@@ -407,12 +650,18 @@ where
                 .peek()
                 .is_some_and(|t| !matches!(t.typ, TokenType::RightBrace))
             {
-                methods.push(self.function()?)
+                let method_doc = self.tokens.peek().copied().and_then(|t| self.doc(t));
+                methods.push(self.function(method_doc, true)?)
             }
 
             self.require_token(TokenType::RightBrace)?;
 
-            Ok(Stmt::Class { name, methods })
+            Ok(Stmt::Class {
+                name,
+                superclass,
+                methods,
+                doc,
+            })
         } else {
             Err(self
                 .tokens
@@ -428,13 +677,13 @@ where
 
     fn expression_statement(&mut self) -> ParserStmtResult<'s> {
         let expr = self.expression()?;
-        self.require_token(TokenType::Semicolon)?;
+        self.require_semicolon()?;
         Ok(Stmt::Expression {
             expr: Box::new(expr),
         })
     }
 
-    fn require_token(&mut self, typ: TokenType<'s>) -> Result<&Token<'s>, ParserError<'s>> {
+    fn require_token(&mut self, typ: TokenType<'s>) -> Result<&'s Token<'s>, ParserError<'s>> {
         self.tokens.next_if(|t| t.typ == typ).ok_or_else(|| {
             self.tokens
                 .peek()
@@ -447,6 +696,29 @@ where
         })
     }
 
+    /// The semicolon that ends a statement. When it's missing, the next token usually starts
+    /// the following statement, so the error points back at the statement that wasn't ended.
+    fn require_semicolon(&mut self) -> Result<&'s Token<'s>, ParserError<'s>> {
+        if let Some(semicolon) = self
+            .tokens
+            .next_if(|t| matches!(t.typ, TokenType::Semicolon))
+        {
+            return Ok(semicolon);
+        }
+
+        // If the next token has been peeked, the last token of the statement is the one before it.
+        let [before, last] = self.recent.get();
+        let previous = if self.tokens.peek().is_some() {
+            before
+        } else {
+            last
+        };
+
+        Err(previous.map_or(ParserError::UnexpectedEndOfInput, |t| {
+            ParserError::MissingSemicolon { after_line: t.line }
+        }))
+    }
+
     fn expression(&mut self) -> ParserExprResult<'s> {
         self.assignment()
     }
@@ -469,6 +741,18 @@ where
                     name,
                     value,
                 })
+            } else if let Expr::Index {
+                object,
+                bracket,
+                index,
+            } = expr
+            {
+                Ok(Expr::SetIndex {
+                    object,
+                    bracket,
+                    index,
+                    value,
+                })
             } else {
                 Err(ParserError::InvalidAssignmentTarget)
             }
@@ -508,12 +792,59 @@ where
     }
 
     fn equality(&mut self) -> ParserExprResult<'s> {
-        let mut expr = self.comparison()?;
+        let mut expr = self.bit_or()?;
 
         while let Some(operator) = self
             .tokens
             .next_if(|t| matches!(t.typ, TokenType::BangEqual | TokenType::EqualEqual))
         {
+            let right = self.bit_or()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    // Among themselves the bitwise operators bind like they do in C, `&` tightest and `|` loosest.
+    // Unlike C, they all bind tighter than equality, so that `x & 1 == 1` means what it looks like.
+    fn bit_or(&mut self) -> ParserExprResult<'s> {
+        let mut expr = self.bit_xor()?;
+
+        while let Some(operator) = self.tokens.next_if(|t| matches!(t.typ, TokenType::Pipe)) {
+            let right = self.bit_xor()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_xor(&mut self) -> ParserExprResult<'s> {
+        let mut expr = self.bit_and()?;
+
+        while let Some(operator) = self.tokens.next_if(|t| matches!(t.typ, TokenType::Caret)) {
+            let right = self.bit_and()?;
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: operator,
+                right: Box::new(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn bit_and(&mut self) -> ParserExprResult<'s> {
+        let mut expr = self.comparison()?;
+
+        while let Some(operator) = self.tokens.next_if(|t| matches!(t.typ, TokenType::Amp)) {
             let right = self.comparison()?;
             expr = Expr::Binary {
                 left: Box::new(expr),
@@ -608,28 +939,12 @@ where
                 .next_if(|t| matches!(t.typ, TokenType::LeftParen))
                 .is_some()
             {
-                let mut args = vec![];
-
-                while self
-                    .tokens
-                    .peek()
-                    .is_some_and(|t| !matches!(t.typ, TokenType::RightParen))
-                {
-                    args.push(self.expression()?);
-                    if !self
-                        .tokens
-                        .peek()
-                        .is_some_and(|t| matches!(t.typ, TokenType::Comma))
-                    {
-                        break;
-                    }
-                }
-
-                self.require_token(TokenType::RightParen)?;
+                let args = self.comma_separated(TokenType::RightParen)?;
+                let paren = self.require_token(TokenType::RightParen)?;
 
                 expr = Expr::Call {
                     callee: Box::new(expr),
-                    // paren,  // trouble with multiple mutable borrows here
+                    paren,
                     args,
                 };
             } else if self
@@ -650,6 +965,38 @@ where
                         msg: "Expected identifier after .".into(),
                     });
                 }
+            } else if let Some(bracket) = self
+                .tokens
+                .next_if(|t| matches!(t.typ, TokenType::LeftBracket))
+            {
+                let start = self.slice_bound(TokenType::Colon)?;
+
+                // Without a colon, this is a plain index rather than a slice.
+                expr = match start {
+                    Some(index)
+                        if self
+                            .tokens
+                            .next_if(|t| matches!(t.typ, TokenType::RightBracket))
+                            .is_some() =>
+                    {
+                        Expr::Index {
+                            object: Box::new(expr),
+                            bracket,
+                            index,
+                        }
+                    }
+                    start => {
+                        self.require_token(TokenType::Colon)?;
+                        let end = self.slice_bound(TokenType::RightBracket)?;
+                        self.require_token(TokenType::RightBracket)?;
+
+                        Expr::Slice {
+                            object: Box::new(expr),
+                            start,
+                            end,
+                        }
+                    }
+                }
             } else {
                 break;
             }
@@ -658,8 +1005,66 @@ where
         Ok(expr)
     }
 
+    /// Expressions separated by commas (allowing a trailing comma), up to but not including
+    /// `terminator`.
+    fn comma_separated(
+        &mut self,
+        terminator: TokenType<'s>,
+    ) -> Result<Vec<Expr<'s>>, ParserError<'s>> {
+        let mut exprs = vec![];
+
+        while self.tokens.peek().is_some_and(|t| t.typ != terminator) {
+            exprs.push(self.expression()?);
+            if self
+                .tokens
+                .next_if(|t| matches!(t.typ, TokenType::Comma))
+                .is_none()
+            {
+                // Anything other than the terminator here is most likely an item missing its comma,
+                // which is a clearer thing to report than the terminator being missing.
+                match self.tokens.peek() {
+                    Some(token) if token.typ != terminator => {
+                        return Err(ParserError::MissingComma { token })
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        Ok(exprs)
+    }
+
+    /// An optional slice endpoint, which is omitted if the next token is `terminator`.
+    fn slice_bound(
+        &mut self,
+        terminator: TokenType<'s>,
+    ) -> Result<Option<Box<Expr<'s>>>, ParserError<'s>> {
+        if self.tokens.peek().is_some_and(|t| t.typ != terminator) {
+            Ok(Some(Box::new(self.expression()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
     fn primary(&mut self) -> ParserExprResult<'s> {
-        if let Some(token) = self.tokens.next() {
+        // Leave a token that can't start an expression in place, since it may well start the next
+        // statement, which error recovery shouldn't skip.
+        if let Some(token) = self.tokens.next_if(|t| {
+            matches!(
+                t.typ,
+                TokenType::False
+                    | TokenType::True
+                    | TokenType::Nil
+                    | TokenType::Number(_)
+                    | TokenType::String(_)
+                    | TokenType::LeftParen
+                    | TokenType::LeftBracket
+                    | TokenType::LeftBrace
+                    | TokenType::Super
+                    | TokenType::This
+                    | TokenType::Identifier(_)
+            )
+        }) {
             Ok(match token.typ {
                 TokenType::False => Expr::Literal { value: token },
                 TokenType::True => Expr::Literal { value: token },
@@ -684,17 +1089,67 @@ where
                         expr: Box::new(expr),
                     }
                 }
+                TokenType::LeftBracket => {
+                    let items = self.comma_separated(TokenType::RightBracket)?;
+                    self.require_token(TokenType::RightBracket)?;
+                    Expr::List { items }
+                }
+                TokenType::LeftBrace => {
+                    let mut entries = vec![];
+
+                    while self
+                        .tokens
+                        .peek()
+                        .is_some_and(|t| !matches!(t.typ, TokenType::RightBrace))
+                    {
+                        let key = self.expression()?;
+                        self.require_token(TokenType::Colon)?;
+                        entries.push((key, self.expression()?));
+                        if self
+                            .tokens
+                            .next_if(|t| matches!(t.typ, TokenType::Comma))
+                            .is_none()
+                        {
+                            break;
+                        }
+                    }
+
+                    self.require_token(TokenType::RightBrace)?;
+                    Expr::Map {
+                        brace: token,
+                        entries,
+                    }
+                }
+                TokenType::Super => {
+                    self.require_token(TokenType::Dot)?;
+                    if let Some(method) = self
+                        .tokens
+                        .next_if(|t| matches!(t.typ, TokenType::Identifier(_)))
+                    {
+                        Expr::Super {
+                            keyword: token,
+                            method,
+                        }
+                    } else {
+                        return Err(ParserError::Error {
+                            msg: "Expected superclass method name after super.".into(),
+                        });
+                    }
+                }
                 TokenType::This => Expr::This { keyword: token },
                 TokenType::Identifier(_) => Expr::Variable { name: token },
-                _ => {
-                    return Err(ParserError::UnexpectedToken {
-                        expected: TokenType::Identifier(""), // TODO: What should this be?
-                        token,
-                    });
-                }
+                _ => unreachable!("Unimplemented expression type"),
             })
         } else {
-            Err(ParserError::UnexpectedEndOfInput)
+            Err(self
+                .tokens
+                .peek()
+                .map_or(ParserError::UnexpectedEndOfInput, |token| {
+                    ParserError::UnexpectedToken {
+                        expected: TokenType::Identifier(""), // TODO: What should this be?
+                        token,
+                    }
+                }))
         }
     }
 }
@@ -703,11 +1158,42 @@ pub fn parse<'s, I>(tokens: I) -> Vec<ParserStmtResult<'s>>
 // TODO: return an iterator instead of a Vec?
 where
     I: IntoIterator<Item = &'s Token<'s>>, // TODO: Iterator or IntoIterator?
+    I::IntoIter: 's,
+{
+    let mut parser = Parser::from(tokens.into_iter());
+    parser.parse()
+}
+
+/// Like [`parse`], but requiring the bodies of `if`, `else`, `while`, and `for` to be blocks,
+/// which catches adding a second statement to a body and forgetting the braces.
+pub fn parse_strict<'s, I>(tokens: I) -> Vec<ParserStmtResult<'s>>
+where
+    I: IntoIterator<Item = &'s Token<'s>>,
+    I::IntoIter: 's,
 {
     let mut parser = Parser::from(tokens.into_iter());
+    parser.strict_braces = true;
     parser.parse()
 }
 
+/// Parse a single bare expression (with no trailing semicolon) that must use up all of the tokens.
+pub fn parse_expression<'s, I>(tokens: I) -> ParserExprResult<'s>
+where
+    I: IntoIterator<Item = &'s Token<'s>>,
+    I::IntoIter: 's,
+{
+    let mut parser = Parser::from(tokens.into_iter());
+    let expr = parser.expression()?;
+
+    match parser.tokens.next() {
+        None => Ok(expr),
+        Some(token) => Err(ParserError::UnexpectedToken {
+            expected: TokenType::Semicolon,
+            token,
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
@@ -722,22 +1208,22 @@ mod tests {
             value: &Token {
                 typ: TokenType::Number(1.0),
                 lexeme: "1",
-                line: 0,
-
+                line: 1,
+                column: 1,
             },
         }),
         op: &Token {
             typ: TokenType::Plus,
             lexeme: "+",
-            line: 0,
-
+            line: 1,
+            column: 3,
         },
         right: Box::new(Expr::Literal {
             value: &Token {
                 typ: TokenType::Number(2.0),
                 lexeme: "2",
-                line: 0,
-
+                line: 1,
+                column: 5,
             },
         }),
         }))]
@@ -747,19 +1233,22 @@ mod tests {
                 value: &Token {
                     typ: TokenType::Number(1.0),
                     lexeme: "1",
-                    line: 0,
+                    line: 1,
+                    column: 2,
                 },
             }),
             op: &Token {
                 typ: TokenType::Plus,
                 lexeme: "+",
-                line: 0,
+                line: 1,
+                column: 4,
             },
             right: Box::new(Expr::Literal {
                 value: &Token {
                     typ: TokenType::Number(2.0),
                     lexeme: "2",
-                    line: 0,
+                    line: 1,
+                    column: 6,
                 },
             }),
         }),
@@ -769,9 +1258,16 @@ mod tests {
             name: &Token {
                 typ: TokenType::Identifier("clock"),
                 lexeme: "clock",
-                line: 0,
+                line: 1,
+                column: 1,
             },
         }),
+        paren: &Token {
+            typ: TokenType::RightParen,
+            lexeme: ")",
+            line: 1,
+            column: 7,
+        },
         args: vec![],
         }))]
     #[case("tsp2cup(15)", Ok(Expr::Call{
@@ -779,16 +1275,58 @@ mod tests {
             name: &Token {
                 typ: TokenType::Identifier("tsp2cup"),
                 lexeme: "tsp2cup",
-                line: 0,
+                line: 1,
+                column: 1,
             },
         }),
         args: vec![Expr::Literal {
             value: &Token {
                 typ: TokenType::Number(15.0),
                 lexeme: "15",
-                line: 0,
+                line: 1,
+                column: 9,
             }}
         ],
+        paren: &Token {
+            typ: TokenType::RightParen,
+            lexeme: ")",
+            line: 1,
+            column: 11,
+        },
+        }))]
+    #[case("xs[0]", Ok(Expr::Index{
+        object: Box::new(Expr::Variable {
+            name: &Token {
+                typ: TokenType::Identifier("xs"),
+                lexeme: "xs",
+                line: 1,
+                column: 1,
+            },
+        }),
+        bracket: &Token {
+            typ: TokenType::LeftBracket,
+            lexeme: "[",
+            line: 1,
+            column: 3,
+        },
+        index: Box::new(Expr::Literal {
+            value: &Token {
+                typ: TokenType::Number(0.0),
+                lexeme: "0",
+                line: 1,
+                column: 4,
+            },
+        }),
+        }))]
+    #[case("[nil,]", Ok(Expr::List{
+        items: vec![Expr::Literal {
+            value: &Token {
+                typ: TokenType::Nil,
+                lexeme: "nil",
+                line: 1,
+                column: 2,
+            },
+        }],
         }))]
     #[case("(1 + 2", Err(ParserError::UnexpectedEndOfInput))]
     #[case("(1 + 2 foo", Err(ParserError::UnexpectedToken{
@@ -796,9 +1334,27 @@ mod tests {
         token: &Token {
             typ: TokenType::Identifier("foo"),
             lexeme: "foo",
-            line: 0,
+            line: 1,
+            column: 8,
         },
         }))]
+    #[case("f(1 2)", Err(ParserError::MissingComma{
+        token: &Token {
+            typ: TokenType::Number(2.0),
+            lexeme: "2",
+            line: 1,
+            column: 5,
+        },
+        }))]
+    #[case("[1, 2 3]", Err(ParserError::MissingComma{
+        token: &Token {
+            typ: TokenType::Number(3.0),
+            lexeme: "3",
+            line: 1,
+            column: 7,
+        },
+        }))]
+    #[case("f(1", Err(ParserError::UnexpectedEndOfInput))]
 
     fn test_parse(#[case] source: &str, #[case] expected: ParserExprResult) {
         let tokens: Vec<Token> = scan(source).try_collect().unwrap();
@@ -813,10 +1369,316 @@ mod tests {
         token: &Token {
             typ: TokenType::Identifier("foo"),
             lexeme: "foo",
-            line: 0,
+            line: 1,
+            column: 8,
+        },
+        }, "Expected ) on line 1, column 8, but got identifier(foo)")]
+    #[case(ParserError::MissingComma{
+        token: &Token {
+            typ: TokenType::Number(2.0),
+            lexeme: "2",
+            line: 1,
+            column: 5,
         },
-        }, "Expected ) on line 0, but got identifier(foo)")]
+        }, "Expected , before number(2) on line 1, column 5")]
     fn test_parse_error_display(#[case] err: ParserError, #[case] expected: &str) {
         assert_eq!(err.to_string(), expected);
     }
+
+    #[rstest]
+    #[case("a.b", "(get a b)")]
+    #[case("a.b = 1", "(set a b 1)")]
+    #[case("a.b().c", "(get ((get a b)) c)")]
+    #[case("a.b.c().d", "(get ((get (get a b) c)) d)")]
+    #[case("a.b.c = d.e", "(set (get a b) c (get d e))")]
+    #[case("a.b().c = 1", "(set ((get a b)) c 1)")]
+    fn test_property_access(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let mut parser = Parser::from(tokens.iter());
+
+        assert_eq!(parser.expression().unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("a.1")]
+    #[case("a.")]
+    #[case("a.b() = 1")]
+    fn test_invalid_property_access(#[case] source: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let mut parser = Parser::from(tokens.iter());
+
+        assert!(parser.expression().is_err());
+    }
+
+    #[test]
+    fn test_field_assignment_parses_to_set() {
+        let tokens: Vec<Token> = scan("foo.field = \"x\"").try_collect().unwrap();
+        let mut parser = Parser::from(tokens.iter());
+
+        let Ok(Expr::Set {
+            object,
+            name,
+            value,
+        }) = parser.expression()
+        else {
+            panic!("Expected a set expression");
+        };
+
+        assert!(matches!(*object, Expr::Variable { name } if name.lexeme == "foo"));
+        assert_eq!(name.lexeme, "field");
+        assert!(matches!(
+            *value,
+            Expr::Literal { value: Token { typ: TokenType::String(s), .. } } if s == "x"
+        ));
+    }
+
+    #[test]
+    fn test_dangling_else_binds_to_innermost_if() {
+        let tokens: Vec<Token> = scan("if (a) if (b) print 1; else print 2;")
+            .try_collect()
+            .unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let [Stmt::If {
+            then: outer_then,
+            els: None,
+            ..
+        }] = stmts.as_slice()
+        else {
+            panic!("Expected an outer if without an else, got {stmts:?}");
+        };
+        let Stmt::If {
+            els: Some(inner_else),
+            ..
+        } = outer_then.as_ref()
+        else {
+            panic!("Expected an inner if with an else, got {outer_then:?}");
+        };
+        assert!(matches!(inner_else.as_ref(), Stmt::Print { .. }));
+    }
+
+    #[rstest]
+    #[case("/// Adds one.\nfun inc(x) { return x + 1; }", Some("Adds one."))]
+    #[case(
+        "/// Adds one\n/// to its argument.\nfun inc(x) { return x + 1; }",
+        Some("Adds one\nto its argument.")
+    )]
+    #[case("// Just a comment.\nfun inc(x) { return x + 1; }", None)]
+    #[case("/// Stale.\n// Interrupted.\nfun inc(x) { return x + 1; }", None)]
+    #[case("fun inc(x) { return x + 1; }", None)]
+    fn test_function_doc_comments(#[case] source: &str, #[case] expected: Option<&str>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let [Stmt::Function { doc, .. }] = stmts.as_slice() else {
+            panic!("Expected a single function, got {stmts:?}");
+        };
+        assert_eq!(doc.as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("class A { m() { return 1; } }", Some(false))]
+    #[case("class A { m { return 1; } }", Some(true))]
+    #[case("fun m { return 1; }", None)]
+    fn test_getters(#[case] source: &str, #[case] expected: Option<bool>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<ParserStmtResult> = parse(tokens.iter());
+
+        let getter = match stmts.as_slice() {
+            [Ok(Stmt::Class { methods, .. })] => match methods.as_slice() {
+                [Stmt::Function { getter, .. }] => Some(*getter),
+                _ => panic!("Expected a single method, got {methods:?}"),
+            },
+            _ => None,
+        };
+        assert_eq!(getter, expected);
+    }
+
+    #[test]
+    fn test_class_and_method_doc_comments() {
+        let source = "/// A point.\nclass Point {\n  /// The x coordinate.\n  x() { return 1; }\n  y() { return 2; }\n}";
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let [Stmt::Class { doc, methods, .. }] = stmts.as_slice() else {
+            panic!("Expected a single class, got {stmts:?}");
+        };
+        assert_eq!(doc.as_deref(), Some("A point."));
+
+        let method_docs = methods
+            .iter()
+            .map(|m| match m {
+                Stmt::Function { doc, .. } => doc.as_deref(),
+                _ => panic!("Expected a method, got {m:?}"),
+            })
+            .collect_vec();
+        assert_eq!(method_docs, vec![Some("The x coordinate."), None]);
+    }
+
+    #[rstest]
+    #[case("if (x) { print 1; }", None)]
+    #[case("if (x) { print 1; } else { print 2; }", None)]
+    #[case("if (x) { print 1; } else if (y) { print 2; } else { print 3; }", None)]
+    #[case("while (x) { print 1; }", None)]
+    #[case("for (;;) { print 1; }", None)]
+    #[case("if (x) a; b;", Some("if"))]
+    #[case("if (x) print 1;", Some("if"))]
+    #[case("if (x) { print 1; } else print 2;", Some("else"))]
+    #[case("while (x) print 1;", Some("while"))]
+    #[case("for (;;) print 1;", Some("for"))]
+    #[case("for (x in xs) print x;", Some("for"))]
+    fn test_strict_braces(#[case] source: &str, #[case] expected: Option<&str>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+
+        assert!(parse(tokens.iter()).into_iter().all(|r| r.is_ok()));
+
+        let error = parse_strict(tokens.iter())
+            .into_iter()
+            .find_map(|r| r.err())
+            .map(|e| match e {
+                ParserError::UnbracedBody { keyword, .. } => keyword,
+                _ => panic!("Expected an unbraced body error, got {e:?}"),
+            });
+        assert_eq!(error, expected);
+    }
+
+    #[rstest]
+    #[case(
+        "switch (x) { case 1: print 1; print 2; case 2: default: print 3; }",
+        Ok("(switch x (case 1 (print 1) (print 2)) (case 2 ) (default (print 3)))")
+    )]
+    #[case("switch (x) {}", Ok("(switch x )"))]
+    #[case(
+        "switch (x) { default: print 1; default: print 2; }",
+        Err("Duplicate default case on line 1")
+    )]
+    #[case("switch (x) { print 1; }", Err("Expected }"))]
+    fn test_switch(#[case] source: &str, #[case] expected: Result<&str, &str>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let result = parse(tokens.iter()).into_iter().next().unwrap();
+
+        match expected {
+            Ok(e) => assert_eq!(result.unwrap().to_string(), e),
+            Err(e) => assert!(result.unwrap_err().to_string().contains(e)),
+        }
+    }
+
+    #[rstest]
+    #[case("for (;;) print 1;", "(while true (print 1))")]
+    #[case("for (; x;) print 1;", "(while x (print 1))")]
+    #[case(
+        "for (; x; x = x + 1) print 1;",
+        "(while x (print 1) (assign x (+ x 1)))"
+    )]
+    #[case(
+        "for (var x = 0; x; x = x + 1) { continue; }",
+        "(block (var x 0) (while x (block (continue)) (assign x (+ x 1))))"
+    )]
+    fn test_for_keeps_increment_out_of_body(#[case] source: &str, #[case] expected: &str) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmt = parse(tokens.iter()).into_iter().next().unwrap().unwrap();
+
+        assert_eq!(stmt.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("for (x in xs) print x;", Ok("(for x xs (print x))"))]
+    #[case(
+        "for (x in xs) { if (x) break; }",
+        Ok("(for x xs (block (if x then (break))))")
+    )]
+    #[case("for (x in xs print x;", Err("Expected )"))]
+    #[case("for (x.y in xs) print 1;", Err("Expected ;"))]
+    #[case("for (var x in xs) print x;", Err("Expected ;"))]
+    fn test_for_in(#[case] source: &str, #[case] expected: Result<&str, &str>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let result = parse(tokens.iter()).into_iter().next().unwrap();
+
+        match expected {
+            Ok(e) => assert_eq!(result.unwrap().to_string(), e),
+            Err(e) => assert!(result.unwrap_err().to_string().contains(e)),
+        }
+    }
+
+    #[rstest]
+    #[case("fun f(a, b, c) {}", Ok("(function f (a b c) ())"))]
+    #[case("f(1, 2, 3);", Ok("(expression (f 1, 2, 3))"))]
+    #[case("f(g(1, 2), 3);", Ok("(expression (f (g 1, 2), 3))"))]
+    #[case("fun f(a b) {}", Err("Expected )"))]
+    #[case("f(1 2);", Err("Expected , before number(2)"))]
+    fn test_comma_separated_lists(#[case] source: &str, #[case] expected: Result<&str, &str>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let result = parse(tokens.iter()).into_iter().next().unwrap();
+
+        match expected {
+            Ok(e) => assert_eq!(result.unwrap().to_string(), e),
+            Err(e) => assert!(result.unwrap_err().to_string().contains(e)),
+        }
+    }
+
+    #[rstest]
+    #[case("print 1\nprint 2;", ParserError::MissingSemicolon { after_line: 1 })]
+    #[case("var x = 1\n\nx = 2;", ParserError::MissingSemicolon { after_line: 1 })]
+    #[case("foo(\n  1,\n  2\n)\nbar();", ParserError::MissingSemicolon { after_line: 4 })]
+    #[case("fun f() { return 1 }", ParserError::MissingSemicolon { after_line: 1 })]
+    #[case("while (true) { break }", ParserError::MissingSemicolon { after_line: 1 })]
+    #[case("print 1", ParserError::MissingSemicolon { after_line: 1 })]
+    fn test_missing_semicolon(#[case] source: &str, #[case] expected: ParserError) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let error = parse(tokens.iter()).into_iter().find_map(|r| r.err());
+
+        assert_eq!(error, Some(expected));
+    }
+
+    #[test]
+    fn test_missing_semicolon_points_at_unfinished_statement() {
+        let source = "print 1\nprint 2;";
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+
+        // The generic error blames the start of the next statement...
+        let mut parser = Parser::from(tokens.iter());
+        parser.tokens.next();
+        parser.expression().unwrap();
+        let generic = parser.require_token(TokenType::Semicolon).unwrap_err();
+        assert_eq!(
+            generic.to_string(),
+            "Expected ; on line 2, column 1, but got print"
+        );
+
+        // ... while the targeted one points back at the statement that wasn't finished.
+        let targeted = parse(tokens.iter())
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            targeted.to_string(),
+            "Expected ; at the end of the statement on line 1"
+        );
+    }
+
+    #[rstest]
+    #[case("print 1\nprint 2;\nvar = 3;\nprint 4;", vec![None, Some("(print 2)"), None, Some("(print 4)")])]
+    #[case("print (1;\nprint 2;", vec![None, Some("(print 2)")])]
+    #[case("1 + ;\n2 + ;\n", vec![None, None])]
+    #[case("var x = 1 var y = 2;\nprint x;", vec![None, Some("(var y 2)"), Some("(print x)")])]
+    #[case("print print;", vec![None, Some("(print)")])]
+    #[case("print 1,;\nprint 2, 3;", vec![None, Some("(print 2 3)")])]
+    #[case("fun f(1) {}\nprint 2;", vec![None, Some("(print 2)")])]
+    fn test_error_recovery(#[case] source: &str, #[case] expected: Vec<Option<&str>>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+
+        let results = parse(tokens.iter())
+            .into_iter()
+            .map(|r| r.map(|s| s.to_string()))
+            .collect_vec();
+
+        assert_eq!(results.len(), expected.len(), "{results:?}");
+        for (result, e) in results.iter().zip(expected) {
+            match e {
+                Some(stmt) => assert_eq!(result.as_deref().unwrap(), stmt),
+                None => assert!(result.is_err(), "{result:?}"),
+            }
+        }
+    }
 }
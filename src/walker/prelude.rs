@@ -0,0 +1,61 @@
+use std::sync::OnceLock;
+
+use crate::{
+    shared::scanner::{scan, Token},
+    walker::{ast::Stmt, parser::parse},
+};
+
+/// Helpers written in Gejang itself, which are defined as globals before every program runs.
+pub const PRELUDE: &str = r#"
+/// The larger of `a` and `b`.
+fun max(a, b) {
+    if (a > b) {
+        return a;
+    }
+    return b;
+}
+
+/// The smaller of `a` and `b`.
+fun min(a, b) {
+    if (a < b) {
+        return a;
+    }
+    return b;
+}
+
+/// `x`, moved into the range from `low` to `high` if it falls outside it.
+fun clamp(x, low, high) {
+    return min(max(x, low), high);
+}
+"#;
+
+/// The syntax tree of the prelude, which is only built once and then shared by every program.
+/// `None` if the prelude itself can't be scanned or parsed.
+pub fn statements() -> Option<&'static [Stmt<'static>]> {
+    static STATEMENTS: OnceLock<Option<Vec<Stmt<'static>>>> = OnceLock::new();
+
+    STATEMENTS
+        .get_or_init(|| {
+            let tokens: Vec<Token> = scan(PRELUDE).collect::<Result<_, _>>().ok()?;
+            let tokens: &'static [Token] = Box::leak(tokens.into_boxed_slice());
+            parse(tokens.iter())
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .ok()
+        })
+        .as_deref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::walker::resolver::resolve;
+
+    #[test]
+    fn test_prelude_is_valid() {
+        let stmts = statements().expect("The prelude failed to scan or parse");
+        let (_, warnings) = resolve(stmts).expect("The prelude failed to resolve");
+
+        assert_eq!(warnings, vec![]);
+    }
+}
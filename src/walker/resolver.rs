@@ -1,5 +1,6 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fmt, fmt::Display, marker::PhantomData, rc::Rc};
 
+use itertools::Itertools;
 use thiserror::Error;
 
 use crate::{
@@ -13,96 +14,208 @@ pub enum ResolutionError {
     Error { msg: String },
 }
 
+/// Legal code that is probably not what the author meant.
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum ResolutionWarning {
+    #[error("Warning on line {line}: assignment to {name} used as a condition (did you mean ==?)")]
+    AssignmentInCondition { name: String, line: usize },
+    #[error("Warning on line {line}: code after {keyword} can never run")]
+    UnreachableCode { keyword: String, line: usize },
+    #[error("Warning on line {line}: local variable {name} is never used")]
+    UnusedVariable { name: String, line: usize },
+}
+
 pub type ResolverResult = Result<(), ResolutionError>;
 
 #[derive(Debug, Clone, PartialEq, Default)]
-pub struct ScopeStack<'s>(Vec<Rc<RefCell<HashMap<&'s str, bool>>>>);
-pub type Locals<'s> = HashMap<&'s Expr<'s>, usize>;
+pub struct ScopeStack<'s>(Vec<Scope<'s>>);
+type Scope<'s> = Rc<RefCell<HashMap<&'s str, Binding<'s>>>>;
+
+/// What the resolver knows about a name declared in a local scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding<'s> {
+    /// Whether the declaration is complete, so the name can be read.
+    defined: bool,
+    used: bool,
+    /// Where the name was declared, if it should be reported when it is never used.
+    /// Parameters and the implicit `this` and `super` don't have one.
+    declaration: Option<&'s Token<'s>>,
+    /// Where the value lives in its scope's environment, which holds locals in declaration order.
+    slot: usize,
+    /// Whether the name was declared by a function declaration,
+    /// which the functions declared before it in the same scope can call.
+    function: bool,
+}
+
+impl Binding<'_> {
+    /// A binding that is always available and never reported as unused.
+    /// It is the only name in its scope, so it always gets the first slot.
+    fn implicit() -> Self {
+        Binding {
+            defined: true,
+            used: false,
+            declaration: None,
+            slot: 0,
+            function: false,
+        }
+    }
+}
+
+/// A name used in a function that no enclosing scope had declared when the function was resolved.
+/// If a function declared later in one of those enclosing scopes has that name, the use refers to it,
+/// so that sibling functions can call each other; otherwise it is left for the global environment.
+#[derive(Debug, Clone, PartialEq)]
+struct LateBinding<'s> {
+    expr: &'s Expr<'s>,
+    name: &'s Token<'s>,
+    /// Where the use was recorded in the resolver's list of resolutions.
+    resolution: usize,
+    /// The scope of the innermost function the name was used in.
+    /// Only scopes outside of that function can declare the name later.
+    function_scope: usize,
+    /// Only the scopes below this one on the stack still enclose the use.
+    enclosing: usize,
+}
+
+/// Where a local variable lives: which scope on the stack, and which slot within it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+pub type Locals<'s> = HashMap<ExprId<'s>, Slot>;
+
+/// Identifies an expression by its address rather than its contents.
+/// Hashing the derived structure would walk the whole subtree on every lookup,
+/// and would conflate identical expressions that live in different scopes.
+/// The address is stable because the AST is borrowed for the whole of `'s`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId<'s>(usize, PhantomData<&'s Expr<'s>>);
+
+impl<'s> From<&'s Expr<'s>> for ExprId<'s> {
+    fn from(expr: &'s Expr<'s>) -> Self {
+        ExprId(expr as *const Expr as usize, PhantomData)
+    }
+}
+
+/// A record of how a single variable, assignment, or `this` expression was resolved.
+/// A slot of `None` means the name was left for the global environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution<'s> {
+    pub expr: &'s Expr<'s>,
+    pub name: &'s Token<'s>,
+    pub slot: Option<Slot>,
+}
+
+impl Display for Resolution<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.slot {
+            Some(s) => write!(f, "{}: {} -> {}", self.name.line, self.expr, s.depth),
+            None => write!(f, "{}: {} -> global", self.name.line, self.expr),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq)]
 enum FunctionType {
     Function,
     Method,
+    Initializer,
 }
 
 #[derive(Debug, PartialEq)]
 enum ClassType {
     Class,
+    Subclass,
 }
 
-impl ScopeStack<'_> {
+impl<'s> ScopeStack<'s> {
     fn push(&mut self) {
         self.0.push(Rc::new(RefCell::new(HashMap::new())))
     }
 
-    fn pop(&mut self) {
-        self.0.pop();
+    fn pop(&mut self) -> Option<Scope<'s>> {
+        self.0.pop()
     }
 }
 #[derive(PartialEq, Debug, Default)]
 struct Resolver<'s> {
     scopes: RefCell<ScopeStack<'s>>,
     locals: RefCell<Locals<'s>>,
+    resolutions: RefCell<Vec<Resolution<'s>>>,
+    warnings: RefCell<Vec<ResolutionWarning>>,
     current_function_type: RefCell<Option<FunctionType>>,
     current_class_type: RefCell<Option<ClassType>>,
+    /// How many loops enclose the current statement, within the current function.
+    loop_depth: RefCell<usize>,
+    /// The scope of the innermost function being resolved, if any.
+    function_scope: RefCell<Option<usize>>,
+    late_bindings: RefCell<Vec<LateBinding<'s>>>,
 }
 
 impl<'s> Resolver<'s> {
     fn resolve_statement(&self, stmt: &'s Stmt<'s>) -> ResolverResult {
         match stmt {
-            Stmt::Block { stmts } => {
-                self.scopes.borrow_mut().push();
-
-                for s in stmts {
-                    self.resolve_statement(s)?
+            Stmt::Block { stmts } => self.resolve_block(stmts)?,
+            Stmt::Switch {
+                subject,
+                cases,
+                default,
+            } => {
+                self.resolve_expression(subject)?;
+                for (value, body) in cases {
+                    self.resolve_expression(value)?;
+                    self.resolve_block(body)?;
+                }
+                if let Some(d) = default {
+                    self.resolve_block(d)?;
+                }
+            }
+            Stmt::Break { keyword } | Stmt::Continue { keyword } => {
+                if *self.loop_depth.borrow() == 0 {
+                    return Err(ResolutionError::Error {
+                        msg: format!("Cannot use '{}' outside a loop", keyword.lexeme),
+                    });
                 }
-
-                self.scopes.borrow_mut().pop();
             }
-            Stmt::Break => {}
             Stmt::Expression { expr } => self.resolve_expression(expr)?,
-            Stmt::Function { name, params, body } => {
-                let enclosing_function_type = self
-                    .current_function_type
-                    .replace(Some(FunctionType::Function));
-
-                self.declare(name)?;
+            Stmt::Function { name, .. } => {
+                self.declare_function(name)?;
                 self.define(name);
 
-                self.scopes.borrow_mut().push();
-
-                for token in params {
-                    self.declare(token)?;
-                    self.define(token);
-                }
-
-                for s in body {
-                    self.resolve_statement(s)?
-                }
-
-                self.scopes.borrow_mut().pop();
-
-                self.current_function_type.replace(enclosing_function_type);
+                self.resolve_function(stmt, FunctionType::Function)?;
             }
             Stmt::If {
                 condition,
                 then,
                 els,
             } => {
+                self.check_condition(condition);
                 self.resolve_expression(condition)?;
-                self.resolve_statement(then)?;
+                self.resolve_body(then)?;
                 if let Some(e) = els {
-                    self.resolve_statement(e)?;
+                    self.resolve_body(e)?;
                 }
             }
-            Stmt::Print { expr } => {
-                self.resolve_expression(expr)?;
+            Stmt::Print { exprs } => {
+                for e in exprs {
+                    self.resolve_expression(e)?;
+                }
             }
-            Stmt::Return { value } => {
-                if self.current_function_type.borrow().is_none() {
-                    return Err(ResolutionError::Error {
-                        msg: "Cannot return from global scope".into(),
-                    });
+            Stmt::Return { value, .. } => {
+                match *self.current_function_type.borrow() {
+                    None => {
+                        return Err(ResolutionError::Error {
+                            msg: "Cannot return from global scope".into(),
+                        })
+                    }
+                    Some(FunctionType::Initializer) if value.is_some() => {
+                        return Err(ResolutionError::Error {
+                            msg: "Cannot return a value from an initializer".into(),
+                        })
+                    }
+                    _ => {}
                 }
 
                 if let Some(v) = value {
@@ -116,36 +229,96 @@ impl<'s> Resolver<'s> {
                 }
                 self.define(name);
             }
-            Stmt::While { condition, body } => {
+            Stmt::ForIn {
+                name,
+                iterable,
+                body,
+            } => {
+                self.resolve_expression(iterable)?;
+
+                self.scopes.borrow_mut().push();
+                // Like a parameter, the loop variable isn't reported if unused,
+                // since a loop may only care how many items there are.
+                self.declare_parameter(name)?;
+                self.define(name);
+
+                *self.loop_depth.borrow_mut() += 1;
+                self.resolve_body(body)?;
+                *self.loop_depth.borrow_mut() -= 1;
+
+                self.end_scope();
+            }
+            Stmt::While {
+                condition,
+                body,
+                increment,
+            } => {
+                self.check_condition(condition);
                 self.resolve_expression(condition)?;
-                self.resolve_statement(body)?;
+                *self.loop_depth.borrow_mut() += 1;
+                self.resolve_body(body)?;
+                *self.loop_depth.borrow_mut() -= 1;
+                if let Some(i) = increment {
+                    self.resolve_expression(i)?;
+                }
             }
-            Stmt::Class { name, methods } => {
+            Stmt::Class {
+                name,
+                superclass,
+                methods,
+                ..
+            } => {
                 let enclosing_class_type = self.current_class_type.replace(Some(ClassType::Class));
 
                 self.declare(name)?;
                 self.define(name);
 
+                if let Some(s) = superclass {
+                    if let Expr::Variable { name: super_name } = s.as_ref() {
+                        if super_name.lexeme == name.lexeme {
+                            return Err(ResolutionError::Error {
+                                msg: "A class can't inherit from itself".into(),
+                            });
+                        }
+                    }
+                    self.resolve_expression(s)?;
+
+                    self.current_class_type.replace(Some(ClassType::Subclass));
+
+                    self.scopes.borrow_mut().push();
+
+                    self.scopes
+                        .borrow_mut()
+                        .0
+                        .last_mut()
+                        .map(|s| s.borrow_mut().insert("super", Binding::implicit()));
+                }
+
                 self.scopes.borrow_mut().push();
 
                 self.scopes
                     .borrow_mut()
                     .0
                     .last_mut()
-                    .map(|s| s.borrow_mut().insert("this", true));
+                    .map(|s| s.borrow_mut().insert("this", Binding::implicit()));
 
                 for method in methods {
-                    let enclosing_function_type = self
-                        .current_function_type
-                        .replace(Some(FunctionType::Method));
+                    let typ = match method {
+                        Stmt::Function { name, .. } if name.lexeme == "init" => {
+                            FunctionType::Initializer
+                        }
+                        _ => FunctionType::Method,
+                    };
+
+                    self.resolve_function(method, typ)?;
+                }
 
-                    self.resolve_statement(method)?;
+                self.end_scope();
 
-                    self.current_function_type.replace(enclosing_function_type);
+                if superclass.is_some() {
+                    self.end_scope();
                 }
 
-                self.scopes.borrow_mut().pop();
-
                 self.current_class_type.replace(enclosing_class_type);
             }
         }
@@ -153,6 +326,64 @@ impl<'s> Resolver<'s> {
         Ok(())
     }
 
+    fn resolve_function(&self, function: &'s Stmt<'s>, typ: FunctionType) -> ResolverResult {
+        let Stmt::Function { params, body, .. } = function else {
+            unreachable!("Tried to resolve a non-function as a function")
+        };
+
+        let enclosing_function_type = self.current_function_type.replace(Some(typ));
+        // A loop outside the function can't be broken out of from inside it.
+        let enclosing_loop_depth = self.loop_depth.replace(0);
+
+        self.scopes.borrow_mut().push();
+        let enclosing_function_scope = self
+            .function_scope
+            .replace(Some(self.scopes.borrow().0.len() - 1));
+
+        for token in params {
+            self.declare_parameter(token)?;
+            self.define(token);
+        }
+
+        self.check_reachable(body);
+        for s in body {
+            self.resolve_statement(s)?
+        }
+
+        self.end_scope();
+
+        self.current_function_type.replace(enclosing_function_type);
+        self.loop_depth.replace(enclosing_loop_depth);
+        self.function_scope.replace(enclosing_function_scope);
+
+        Ok(())
+    }
+
+    /// Resolve the body of an `if`, `else`, or `while`.
+    /// A declaration there might never run, so it gets a scope of its own
+    /// instead of a slot in the enclosing scope that might never be filled.
+    fn resolve_body(&self, stmt: &'s Stmt<'s>) -> ResolverResult {
+        if matches!(stmt, Stmt::Function { .. } | Stmt::Class { .. }) {
+            self.resolve_block(std::slice::from_ref(stmt))
+        } else {
+            self.resolve_statement(stmt)
+        }
+    }
+
+    /// Resolve statements that run in a scope of their own, like the body of a block.
+    fn resolve_block(&self, stmts: &'s [Stmt<'s>]) -> ResolverResult {
+        self.scopes.borrow_mut().push();
+
+        self.check_reachable(stmts);
+        for s in stmts {
+            self.resolve_statement(s)?
+        }
+
+        self.end_scope();
+
+        Ok(())
+    }
+
     fn resolve_expression(&self, expr: &'s Expr<'s>) -> ResolverResult {
         match expr {
             Expr::Assign { name, value } => {
@@ -163,7 +394,7 @@ impl<'s> Resolver<'s> {
                 self.resolve_expression(left)?;
                 self.resolve_expression(right)?;
             }
-            Expr::Call { callee, args } => {
+            Expr::Call { callee, args, .. } => {
                 self.resolve_expression(callee)?;
 
                 for a in args {
@@ -187,7 +418,7 @@ impl<'s> Resolver<'s> {
                     .borrow()
                     .0
                     .last()
-                    .and_then(|s| s.borrow().get(name.lexeme).cloned())
+                    .and_then(|s| s.borrow().get(name.lexeme).map(|b| b.defined))
                 {
                     return Err(ResolutionError::Error {
                         msg: "Cannot read local variable in its own initializer".to_string(),
@@ -199,14 +430,62 @@ impl<'s> Resolver<'s> {
             Expr::Get { object, .. } => {
                 self.resolve_expression(object)?;
             }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expression(object)?;
+                self.resolve_expression(index)?;
+            }
+            Expr::List { items } => {
+                for i in items {
+                    self.resolve_expression(i)?;
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (k, v) in entries {
+                    self.resolve_expression(k)?;
+                    self.resolve_expression(v)?;
+                }
+            }
+            Expr::Slice { object, start, end } => {
+                self.resolve_expression(object)?;
+                for e in [start, end].into_iter().flatten() {
+                    self.resolve_expression(e)?;
+                }
+            }
             Expr::Set { object, value, .. } => {
                 self.resolve_expression(object)?;
                 self.resolve_expression(value)?;
             }
+            Expr::SetIndex {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                self.resolve_expression(object)?;
+                self.resolve_expression(index)?;
+                self.resolve_expression(value)?;
+            }
+            Expr::Super { keyword, .. } => {
+                match *self.current_class_type.borrow() {
+                    None => {
+                        return Err(ResolutionError::Error {
+                            msg: "Cannot use 'super' outside of a class".into(),
+                        })
+                    }
+                    Some(ClassType::Class) => {
+                        return Err(ResolutionError::Error {
+                            msg: "Cannot use 'super' in a class with no superclass".into(),
+                        })
+                    }
+                    Some(ClassType::Subclass) => {}
+                }
+
+                self.resolve_local(expr, keyword);
+            }
             Expr::This { keyword } => {
                 if self.current_class_type.borrow().is_none() {
                     return Err(ResolutionError::Error {
-                        msg: "Cannot use 'this' outside a class".into(),
+                        msg: "Cannot use 'this' outside of a class".into(),
                     });
                 }
 
@@ -217,7 +496,90 @@ impl<'s> Resolver<'s> {
         Ok(())
     }
 
+    /// `if (x = 1)` is legal, but is much more likely to be a typo for `if (x == 1)`.
+    fn check_condition(&self, condition: &Expr<'s>) {
+        if let Expr::Assign { name, .. } = condition {
+            self.warnings
+                .borrow_mut()
+                .push(ResolutionWarning::AssignmentInCondition {
+                    name: name.lexeme.to_string(),
+                    line: name.line,
+                });
+        }
+    }
+
+    /// Statements after a `return`, `break`, or `continue` in the same block can never run.
+    /// Only the block's own statements count, so a `return` inside an `if` doesn't make the code
+    /// after the `if` unreachable.
+    fn check_reachable(&self, stmts: &[Stmt<'s>]) {
+        let jump = stmts[..stmts.len().saturating_sub(1)]
+            .iter()
+            .find_map(|s| match s {
+                Stmt::Return { keyword, .. }
+                | Stmt::Break { keyword }
+                | Stmt::Continue { keyword } => Some(keyword),
+                _ => None,
+            });
+
+        if let Some(keyword) = jump {
+            self.warnings
+                .borrow_mut()
+                .push(ResolutionWarning::UnreachableCode {
+                    keyword: keyword.lexeme.to_string(),
+                    line: keyword.line,
+                });
+        }
+    }
+
+    /// Pop the innermost scope, warning about any names declared in it that were never used.
+    fn end_scope(&self) {
+        self.bind_late();
+
+        let Some(scope) = self.scopes.borrow_mut().pop() else {
+            return;
+        };
+
+        let unused = scope
+            .borrow()
+            .values()
+            .filter(|b| !b.used)
+            .filter_map(|b| b.declaration)
+            .sorted_by_key(|t| (t.line, t.column))
+            .map(|t| ResolutionWarning::UnusedVariable {
+                name: t.lexeme.to_string(),
+                line: t.line,
+            })
+            .collect_vec();
+
+        self.warnings.borrow_mut().extend(unused);
+    }
+
     fn declare(&self, name: &'s Token<'s>) -> ResolverResult {
+        self.declare_binding(name, Some(name))
+    }
+
+    fn declare_function(&self, name: &'s Token<'s>) -> ResolverResult {
+        self.declare(name)?;
+        self.scopes.borrow_mut().0.last_mut().and_then(|s| {
+            s.borrow_mut()
+                .get_mut(name.lexeme)
+                .map(|b| b.function = true)
+        });
+
+        Ok(())
+    }
+
+    /// Parameters are never reported as unused,
+    /// since a callback may need to accept arguments it doesn't care about.
+    fn declare_parameter(&self, name: &'s Token<'s>) -> ResolverResult {
+        self.declare_binding(name, None)
+    }
+
+    fn declare_binding(
+        &self,
+        name: &'s Token<'s>,
+        declaration: Option<&'s Token<'s>>,
+    ) -> ResolverResult {
         self.scopes
             .borrow_mut()
             .0
@@ -229,7 +591,17 @@ impl<'s> Resolver<'s> {
                         msg: format!("Variable {} was already defined in this scope", name.lexeme),
                     })
                 } else {
-                    scope.insert(name.lexeme, false);
+                    let slot = scope.len();
+                    scope.insert(
+                        name.lexeme,
+                        Binding {
+                            defined: false,
+                            used: false,
+                            declaration,
+                            slot,
+                            function: false,
+                        },
+                    );
                     Ok(())
                 }
             })
@@ -237,36 +609,403 @@ impl<'s> Resolver<'s> {
     }
 
     fn define(&self, name: &'s Token<'s>) {
-        self.scopes
-            .borrow_mut()
-            .0
-            .last_mut()
-            .map(|s| s.borrow_mut().insert(name.lexeme, true));
+        self.scopes.borrow_mut().0.last_mut().and_then(|s| {
+            s.borrow_mut()
+                .get_mut(name.lexeme)
+                .map(|b| b.defined = true)
+        });
     }
 
     fn resolve_local(&self, expr: &'s Expr<'s>, name: &'s Token<'s>) {
-        if let Some(depth) = self
+        let slot = self
             .scopes
             .borrow()
             .0
             .iter()
-            .rposition(|s| s.borrow().contains_key(name.lexeme))
-        {
-            self.locals.borrow_mut().insert(expr, depth);
+            .enumerate()
+            .rev()
+            .find_map(|(depth, s)| {
+                s.borrow_mut().get_mut(name.lexeme).map(|b| {
+                    b.used = true;
+                    Slot {
+                        depth,
+                        index: b.slot,
+                    }
+                })
+            });
+
+        if let Some(s) = slot {
+            self.locals.borrow_mut().insert(ExprId::from(expr), s);
+        }
+
+        let mut resolutions = self.resolutions.borrow_mut();
+        if let (None, Some(function_scope)) = (slot, *self.function_scope.borrow()) {
+            self.late_bindings.borrow_mut().push(LateBinding {
+                expr,
+                name,
+                resolution: resolutions.len(),
+                function_scope,
+                enclosing: self.scopes.borrow().0.len(),
+            });
         }
+        resolutions.push(Resolution { expr, name, slot });
+    }
+
+    /// Before the innermost scope is popped, point the late bindings it still encloses
+    /// at the functions it declared. The rest are now only enclosed by the scopes below it.
+    fn bind_late(&self) {
+        let scopes = self.scopes.borrow();
+        let Some((depth, scope)) = scopes.0.iter().enumerate().next_back() else {
+            return;
+        };
+
+        self.late_bindings.borrow_mut().retain_mut(|late| {
+            if late.enclosing <= depth {
+                return true;
+            }
+
+            let binding = scope
+                .borrow_mut()
+                .get_mut(late.name.lexeme)
+                .filter(|b| b.function && late.function_scope > depth)
+                .map(|b| {
+                    b.used = true;
+                    b.slot
+                });
+
+            match binding {
+                Some(index) => {
+                    let slot = Slot { depth, index };
+                    self.locals
+                        .borrow_mut()
+                        .insert(ExprId::from(late.expr), slot);
+                    self.resolutions.borrow_mut()[late.resolution].slot = Some(slot);
+                    false
+                }
+                // Once no local scope encloses the use, it is a global after all.
+                None if depth == 0 => false,
+                None => {
+                    late.enclosing = depth;
+                    true
+                }
+            }
+        });
     }
 
     fn locals(self) -> Locals<'s> {
         self.locals.into_inner()
     }
+
+    fn resolutions(self) -> Vec<Resolution<'s>> {
+        self.resolutions.into_inner()
+    }
 }
 
-pub fn resolve<'s>(stmts: &'s [Stmt<'s>]) -> Result<Locals<'s>, ResolutionError> {
+fn run<'s>(stmts: &'s [Stmt<'s>]) -> Result<Resolver<'s>, ResolutionError> {
     let resolver = Resolver::default();
 
     for stmt in stmts {
         resolver.resolve_statement(stmt)?;
     }
 
-    Ok(resolver.locals())
+    Ok(resolver)
+}
+
+/// Find the scope depth and slot of each local variable reference,
+/// along with any warnings about suspicious (but legal) code.
+pub fn resolve<'s>(
+    stmts: &'s [Stmt<'s>],
+) -> Result<(Locals<'s>, Vec<ResolutionWarning>), ResolutionError> {
+    let resolver = run(stmts)?;
+    let warnings = resolver.warnings.take();
+    Ok((resolver.locals(), warnings))
+}
+
+/// Like [`resolve`], but returns every resolution in the order it was made,
+/// including the ones that fall through to the global environment.
+pub fn resolutions<'s>(stmts: &'s [Stmt<'s>]) -> Result<Vec<Resolution<'s>>, ResolutionError> {
+    Ok(run(stmts)?.resolutions())
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{shared::scanner::scan, walker::parser::parse};
+
+    #[test]
+    fn test_identical_expressions_resolve_independently() {
+        let source = "{ var a = 1; { var a = 2; print a; } print a; }";
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let (locals, _) = resolve(&stmts).unwrap();
+
+        let Stmt::Block { stmts: outer } = &stmts[0] else {
+            panic!("Expected a block");
+        };
+        let Stmt::Block { stmts: inner } = &outer[1] else {
+            panic!("Expected a block");
+        };
+        let (Stmt::Print { exprs: inner_a }, Stmt::Print { exprs: outer_a }) =
+            (&inner[1], &outer[2])
+        else {
+            panic!("Expected print statements");
+        };
+        let ([inner_a], [outer_a]) = (inner_a.as_slice(), outer_a.as_slice()) else {
+            panic!("Expected one value printed by each");
+        };
+
+        // The same code...
+        assert_eq!(inner_a.to_string(), outer_a.to_string());
+        // ... but resolved separately.
+        assert_eq!(
+            locals.get(&ExprId::from(inner_a)),
+            Some(&Slot { depth: 1, index: 0 })
+        );
+        assert_eq!(
+            locals.get(&ExprId::from(outer_a)),
+            Some(&Slot { depth: 0, index: 0 })
+        );
+    }
+
+    #[rstest]
+    #[case("class A { m(x) { print this; print x; } }", vec![("this", Some(0)), ("x", Some(1))])]
+    #[case(
+        "class A { m(x) { var y = x; print this; print y; } }",
+        vec![("x", Some(1)), ("this", Some(0)), ("y", Some(1))]
+    )]
+    #[case(
+        "{ class A { m() { fun inner(z) { print this; print z; } } } }",
+        vec![("this", Some(1)), ("z", Some(3))]
+    )]
+    #[case("class A { m() { print x; } }", vec![("x", None)])]
+    #[case("class A { m() { return this.field; } }", vec![("this", Some(0))])]
+    #[case(
+        "class A {} class B < A { m() { return this.field; } }",
+        vec![("A", None), ("this", Some(1))]
+    )]
+    fn test_method_resolution(#[case] source: &str, #[case] expected: Vec<(&str, Option<usize>)>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let depths = resolutions(&stmts)
+            .unwrap()
+            .iter()
+            .map(|r| (r.name.lexeme, r.slot.map(|s| s.depth)))
+            .collect_vec();
+
+        assert_eq!(depths, expected);
+    }
+
+    #[rstest]
+    #[case("{ var a; var b; print b; print a; }", vec![("b", Some(1)), ("a", Some(0))])]
+    #[case("fun f(x, y) { var z; print z; print y; }", vec![("z", Some(2)), ("y", Some(1))])]
+    #[case(
+        "{ var a; { var b; var c; print c; print a; } }",
+        vec![("c", Some(1)), ("a", Some(0))]
+    )]
+    #[case(
+        "{ var a; fun f() {} f(); print a; }",
+        vec![("f", Some(1)), ("a", Some(0))]
+    )]
+    #[case("fun f(x) { var y; fun g() {} g(); print y; }", vec![("g", Some(2)), ("y", Some(1))])]
+    #[case("{ var a; fun f() { g(); } fun g() {} print a; }", vec![("g", Some(2)), ("a", Some(0))])]
+    #[case("{ fun f() { x; } var x; }", vec![("x", None)])]
+    #[case("{ fun f() { g(); } { fun g() {} } }", vec![("g", None)])]
+    #[case("fun f() { g(); fun g() {} }", vec![("g", None)])]
+    #[case("class A {} class B < A { m() { super.m(); } }", vec![("A", None), ("super", Some(0))])]
+    #[case("var g; print g;", vec![("g", None)])]
+    fn test_slot_indices(#[case] source: &str, #[case] expected: Vec<(&str, Option<usize>)>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let indices = resolutions(&stmts)
+            .unwrap()
+            .iter()
+            .map(|r| (r.name.lexeme, r.slot.map(|s| s.index)))
+            .collect_vec();
+
+        assert_eq!(indices, expected);
+    }
+
+    #[rstest]
+    #[case("class A { m() { print this; } }", Ok(()))]
+    #[case("class A { m() { fun f() { return this; } } }", Ok(()))]
+    #[case(
+        "print this;",
+        Err(ResolutionError::Error { msg: "Cannot use 'this' outside of a class".into() })
+    )]
+    #[case(
+        "fun f() { print this; }",
+        Err(ResolutionError::Error { msg: "Cannot use 'this' outside of a class".into() })
+    )]
+    #[case(
+        "class A {} fun f() { print this; }",
+        Err(ResolutionError::Error { msg: "Cannot use 'this' outside of a class".into() })
+    )]
+    fn test_this_outside_class_is_an_error(
+        #[case] source: &str,
+        #[case] expected: Result<(), ResolutionError>,
+    ) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        assert_eq!(resolve(&stmts).map(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case("class A {} class B < A {}", Ok(()))]
+    #[case(
+        "class A < A {}",
+        Err(ResolutionError::Error { msg: "A class can't inherit from itself".into() })
+    )]
+    #[case("class A { m() {} } class B < A { m() { super.m(); } }", Ok(()))]
+    #[case(
+        "super.m();",
+        Err(ResolutionError::Error { msg: "Cannot use 'super' outside of a class".into() })
+    )]
+    #[case(
+        "class A { m() { super.m(); } }",
+        Err(ResolutionError::Error { msg: "Cannot use 'super' in a class with no superclass".into() })
+    )]
+    fn test_inheritance(#[case] source: &str, #[case] expected: Result<(), ResolutionError>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        assert_eq!(resolve(&stmts).map(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case("while (true) break;", Ok(()))]
+    #[case("for (;;) { if (true) continue; break; }", Ok(()))]
+    #[case("while (true) { while (true) break; break; }", Ok(()))]
+    #[case("for (x in []) { if (x) continue; break; }", Ok(()))]
+    #[case(
+        "break;",
+        Err(ResolutionError::Error { msg: "Cannot use 'break' outside a loop".into() })
+    )]
+    #[case(
+        "{ continue; }",
+        Err(ResolutionError::Error { msg: "Cannot use 'continue' outside a loop".into() })
+    )]
+    #[case(
+        "while (true) { fun f() { break; } }",
+        Err(ResolutionError::Error { msg: "Cannot use 'break' outside a loop".into() })
+    )]
+    #[case(
+        "for (x in []) { fun f() { continue; } }",
+        Err(ResolutionError::Error { msg: "Cannot use 'continue' outside a loop".into() })
+    )]
+    fn test_loop_control_outside_loop(
+        #[case] source: &str,
+        #[case] expected: Result<(), ResolutionError>,
+    ) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        assert_eq!(resolve(&stmts).map(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case("class A { init() { return; } }", Ok(()))]
+    #[case("class A { m() { return 1; } }", Ok(()))]
+    #[case("class A { init() { fun f() { return 1; } } }", Ok(()))]
+    #[case("fun init() { return 1; }", Ok(()))]
+    #[case("class A { m() { fun init() { return 1; } } }", Ok(()))]
+    #[case(
+        "class A { init() { return 1; } }",
+        Err(ResolutionError::Error { msg: "Cannot return a value from an initializer".into() })
+    )]
+    fn test_initializer_returns(
+        #[case] source: &str,
+        #[case] expected: Result<(), ResolutionError>,
+    ) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        assert_eq!(resolve(&stmts).map(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case("var x; if (x = 1) print x;", vec![ResolutionWarning::AssignmentInCondition{name: "x".into(), line: 1}])]
+    #[case("var x; while (x = nil) print x;", vec![ResolutionWarning::AssignmentInCondition{name: "x".into(), line: 1}])]
+    #[case("var x; if (x == 1) print x;", vec![])]
+    #[case("var x; if ((x = 1)) print x;", vec![])]
+    #[case("var x; x = 1; if (x) print x;", vec![])]
+    fn test_assignment_in_condition_warning(
+        #[case] source: &str,
+        #[case] expected: Vec<ResolutionWarning>,
+    ) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let (_, warnings) = resolve(&stmts).unwrap();
+
+        assert_eq!(warnings, expected);
+    }
+
+    #[rstest]
+    #[case("fun f() { return 1; print 2; }", vec![("return", 1)])]
+    #[case("fun f() {\n  return;\n  print 1;\n  print 2;\n}", vec![("return", 2)])]
+    #[case("while (true) { break; print 1; }", vec![("break", 1)])]
+    #[case("while (true) { continue; print 1; }", vec![("continue", 1)])]
+    #[case("fun f() { { return 1; print 2; } }", vec![("return", 1)])]
+    #[case("switch (1) { case 1: { while (true) { break; print 1; } } }", vec![("break", 1)])]
+    #[case("fun f() { return 1; }", vec![])]
+    #[case("fun f(x) { if (x) return 1; print 2; }", vec![])]
+    #[case("fun f(x) { if (x) { return 1; } else { return 2; } print 3; }", vec![])]
+    #[case("while (true) { if (true) break; print 1; }", vec![])]
+    #[case("for (var i = 0; i < 3; i = i + 1) { print i; continue; }", vec![])]
+    fn test_unreachable_code_warning(#[case] source: &str, #[case] expected: Vec<(&str, usize)>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let (_, warnings) = resolve(&stmts).unwrap();
+
+        assert_eq!(
+            warnings,
+            expected
+                .into_iter()
+                .map(|(keyword, line)| ResolutionWarning::UnreachableCode {
+                    keyword: keyword.into(),
+                    line
+                })
+                .collect_vec()
+        );
+    }
+
+    #[rstest]
+    #[case("{ var x = 1; print x; }", vec![])]
+    #[case("{ var x = 1; }", vec![("x", 1)])]
+    #[case("{\n  var used = 1;\n  var b;\n  var a;\n  print used;\n}", vec![("b", 3), ("a", 4)])]
+    #[case("{ var x; x = 1; }", vec![])]
+    #[case("{ var x = 1; fun f() { return x; } }", vec![("f", 1)])]
+    #[case("{ class A {} }", vec![("A", 1)])]
+    #[case("{ var x = 1; { var x = 2; print x; } }", vec![("x", 1)])]
+    #[case("fun f(unused) { var y; }", vec![("y", 1)])]
+    #[case("fun f(a) { return a; }", vec![])]
+    #[case("class A { m() { return this; } }", vec![])]
+    #[case("class A {} class B < A { m() { return super.m; } }", vec![])]
+    #[case("var global; fun f() {}", vec![])]
+    #[case("for (var i = 0; i < 3; i = i + 1) {}", vec![])]
+    fn test_unused_variable_warning(#[case] source: &str, #[case] expected: Vec<(&str, usize)>) {
+        let tokens: Vec<Token> = scan(source).try_collect().unwrap();
+        let stmts: Vec<Stmt> = parse(tokens.iter()).into_iter().try_collect().unwrap();
+
+        let (_, warnings) = resolve(&stmts).unwrap();
+
+        assert_eq!(
+            warnings,
+            expected
+                .into_iter()
+                .map(|(name, line)| ResolutionWarning::UnusedVariable {
+                    name: name.into(),
+                    line
+                })
+                .collect_vec()
+        );
+    }
 }
@@ -1,53 +1,117 @@
 use std::{
     borrow::Cow,
+    cell::RefCell,
+    cmp::Ordering,
     collections::HashMap,
     fmt::{Debug, Display},
+    rc::Rc,
 };
 
+use itertools::Itertools;
 use strum_macros::{AsRefStr, IntoStaticStr};
 
 use crate::{
-    shared::scanner::TokenType,
+    shared::{numbers::format_number, scanner::TokenType},
     walker::{
         ast::Stmt,
-        interpreter::{EnvironmentStack, LoxPointer},
+        interpreter::{EnvironmentStack, LoxPointer, NativeFunction},
     },
 };
 
 #[derive(Debug, Clone, PartialEq, AsRefStr, IntoStaticStr)]
+// The derived PartialEq compares native functions by address, which the compiler warns isn't reliable.
+// Lox's own equality is lox_eq, which doesn't rely on it.
+#[allow(unpredictable_function_pointer_comparisons)]
 pub enum Value<'s> {
-    // Is it really worth bringing those strings all the way from the source to here?
-    #[allow(dead_code)]
     Number(f64),
-    String(Cow<'s, str>),
+    /// Strings built by the interpreter are interned, so equal ones usually share storage.
+    String(Rc<str>),
     Boolean(bool),
     Nil,
     NativeFunction {
         name: &'static str,
         arity: usize,
-        f: fn(&[LoxPointer<'s>]) -> LoxPointer<'s>,
+        /// How many more arguments than `arity` the function will also accept.
+        optional: usize,
+        f: NativeFunction<'s>,
     },
     Function {
         name: &'s str,
-        params: Vec<&'s str>,
+        params: Rc<[&'s str]>,
         body: &'s Vec<Stmt<'s>>,
         closure: EnvironmentStack<'s>,
+        /// A class's `init` method, which always returns the instance it is bound to.
+        initializer: bool,
+        /// A method that is called as soon as it is accessed on an instance.
+        getter: bool,
     },
     Class {
         name: &'s str,
+        superclass: Option<LoxPointer<'s>>,
         methods: HashMap<Cow<'s, str>, LoxPointer<'s>>,
     },
     Instance {
-        class: Box<LoxPointer<'s>>,
+        /// The class this is an instance of, shared with every other instance of it.
+        class: LoxPointer<'s>,
         fields: HashMap<Cow<'s, str>, LoxPointer<'s>>,
     },
+    /// Lists are shared, so a change made through one reference is seen through every other.
+    List(Rc<RefCell<Vec<LoxPointer<'s>>>>),
+    Map(Rc<RefCell<HashMap<MapKey, LoxPointer<'s>>>>),
+}
+
+/// The values that can be used as map keys.
+/// Numbers are stored by their bits so they can be hashed, with `-0` folded into `0`
+/// so that the two keys agree just like the numbers do.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Number(u64),
+    String(Rc<str>),
+}
+
+impl MapKey {
+    pub fn new(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) if *n == 0.0 => Some(MapKey::Number(0f64.to_bits())),
+            Value::Number(n) => Some(MapKey::Number(n.to_bits())),
+            Value::String(s) => Some(MapKey::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_value<'s>(&self) -> Value<'s> {
+        match self {
+            MapKey::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+/// Numbers sort numerically, before all strings.
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Number(l), MapKey::Number(r)) => {
+                f64::from_bits(*l).total_cmp(&f64::from_bits(*r))
+            }
+            (MapKey::Number(_), MapKey::String(_)) => Ordering::Less,
+            (MapKey::String(_), MapKey::Number(_)) => Ordering::Greater,
+            (MapKey::String(l), MapKey::String(r)) => l.cmp(r),
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl<'s> From<&TokenType<'s>> for Value<'s> {
     fn from(token: &TokenType<'s>) -> Self {
         match token {
             TokenType::Number(value) => Value::Number(*value),
-            TokenType::String(value) => Value::String(Cow::from(*value)),
+            TokenType::String(value) => Value::String(Rc::from(value.as_ref())),
             TokenType::True => Value::Boolean(true),
             TokenType::False => Value::Boolean(false),
             TokenType::Nil => Value::Nil,
@@ -57,6 +121,39 @@ impl<'s> From<&TokenType<'s>> for Value<'s> {
 }
 
 impl Value<'_> {
+    /// Equality as Lox programs see it. Primitives, lists, and maps compare by value,
+    /// but functions, classes, and instances compare by identity,
+    /// since comparing their contents would chase cycles through closures forever.
+    pub fn lox_eq(&self, other: &Self) -> bool {
+        self.lox_eq_within(other, &mut Vec::new())
+    }
+
+    fn lox_eq_within(&self, other: &Self, comparing: &mut Comparing) -> bool {
+        match (self, other) {
+            // IEEE 754 comparison, so NaN is never equal to anything, not even itself.
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::String(l), Value::String(r)) => Rc::ptr_eq(l, r) || l == r,
+            (Value::Boolean(l), Value::Boolean(r)) => l == r,
+            (Value::Nil, Value::Nil) => true,
+            (Value::List(l), Value::List(r)) => contents_eq(l, r, comparing, |comparing| {
+                l.borrow().len() == r.borrow().len()
+                    && l.borrow()
+                        .iter()
+                        .zip(r.borrow().iter())
+                        .all(|(a, b)| pointee_eq(a, b, comparing))
+            }),
+            (Value::Map(l), Value::Map(r)) => contents_eq(l, r, comparing, |comparing| {
+                l.borrow().len() == r.borrow().len()
+                    && l.borrow().iter().all(|(k, a)| {
+                        r.borrow()
+                            .get(k)
+                            .is_some_and(|b| pointee_eq(a, b, comparing))
+                    })
+            }),
+            _ => std::ptr::eq(self, other),
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             // TODO: implement Python-like truthiness
@@ -65,23 +162,104 @@ impl Value<'_> {
             _ => true,
         }
     }
+
+    fn show(&self, showing: &mut Showing) -> String {
+        match self {
+            Value::Number(value) => format_number(*value),
+            Value::String(value) => value.to_string(),
+            Value::Boolean(value) => value.to_string(),
+            Value::Nil => "nil".to_string(),
+            Value::NativeFunction { name, arity, .. } => format!("<native fun {name}/{arity}>"),
+            Value::Function { name, params, .. } => format!("<fun {}/{}>", name, params.len()),
+            Value::Class { name, .. } => format!("<cls {}>", name),
+            Value::Instance { class, .. } => format!("<instance of {}>", class.borrow()),
+            Value::List(items) => show_contents(items, showing, "[...]", |showing| {
+                format!(
+                    "[{}]",
+                    items
+                        .borrow()
+                        .iter()
+                        .map(|i| i.borrow().show(showing))
+                        .join(", ")
+                )
+            }),
+            Value::Map(entries) => show_contents(entries, showing, "{...}", |showing| {
+                format!(
+                    "{{{}}}",
+                    entries
+                        .borrow()
+                        .iter()
+                        .sorted_by(|a, b| a.0.cmp(b.0))
+                        .map(|(k, v)| format!("{}: {}", k.to_value(), v.borrow().show(showing)))
+                        .join(", ")
+                )
+            }),
+        }
+    }
 }
 
+/// The pairs of lists and maps whose comparison is already under way, by address.
+type Comparing = Vec<(*const (), *const ())>;
+
+fn pointee_eq<'s>(l: &LoxPointer<'s>, r: &LoxPointer<'s>, comparing: &mut Comparing) -> bool {
+    Rc::ptr_eq(l, r) || l.borrow().lox_eq_within(&r.borrow(), comparing)
+}
+
+/// Compare the contents of two lists or maps, unless they're the same one.
+/// A list that contains itself would otherwise be compared forever, so meeting a pair
+/// that's already being compared further up counts as equal: if they differ,
+/// the comparison further up will find out somewhere else.
+fn contents_eq<T>(
+    l: &Rc<T>,
+    r: &Rc<T>,
+    comparing: &mut Comparing,
+    contents: impl FnOnce(&mut Comparing) -> bool,
+) -> bool {
+    let pair = (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ());
+    if Rc::ptr_eq(l, r) || comparing.contains(&pair) {
+        return true;
+    }
+
+    comparing.push(pair);
+    let equal = contents(comparing);
+    comparing.pop();
+    equal
+}
+
+/// The lists and maps that are already being shown, by address.
+type Showing = Vec<*const ()>;
+
+/// Show the contents of a list or map, unless it is already being shown further up.
+/// Then it contains itself, and `cycle` stands in for it instead of showing it forever.
+fn show_contents<T>(
+    container: &Rc<T>,
+    showing: &mut Showing,
+    cycle: &str,
+    contents: impl FnOnce(&mut Showing) -> String,
+) -> String {
+    let address = Rc::as_ptr(container) as *const ();
+    if showing.contains(&address) {
+        return cycle.to_string();
+    }
+
+    showing.push(address);
+    let shown = contents(showing);
+    showing.pop();
+    shown
+}
+
+/// The alternate form (`{:#}`) of an instance also lists its field names.
 impl Display for Value<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Value::Number(value) => value.to_string(),
-                Value::String(value) => value.to_string(),
-                Value::Boolean(value) => value.to_string(),
-                Value::Nil => "nil".to_string(),
-                Value::NativeFunction { name, arity, .. } => format!("<native fun {name}/{arity}>"),
-                Value::Function { name, params, .. } => format!("<fun {}/{}>", name, params.len()),
-                Value::Class { name, .. } => format!("<cls {}>", name),
-                Value::Instance { class, .. } => format!("<instance of {}>", class.borrow()), // TODO: implement better object display
-            }
-        )
+        if let (Value::Instance { class, fields }, true) = (self, f.alternate()) {
+            return write!(
+                f,
+                "<instance of {} {{{}}}>",
+                class.borrow(),
+                fields.keys().sorted().join(", ")
+            );
+        }
+
+        write!(f, "{}", self.show(&mut Vec::new()))
     }
 }
@@ -0,0 +1,84 @@
+use gejang::{run, InterpreterError, Options, Streams};
+
+#[test]
+fn test_run_captures_output() {
+    let mut output = Vec::new();
+    let mut error = Vec::new();
+    let mut streams = Streams {
+        input: std::io::empty(),
+        output: &mut output,
+        error: &mut error,
+    };
+
+    run(
+        "fun greet(name) { print \"hello \" + name; } greet(\"embedder\");",
+        &mut streams,
+        Options::default(),
+    )
+    .unwrap();
+
+    assert_eq!(String::from_utf8(output).unwrap(), "hello embedder\n");
+    assert!(error.is_empty());
+}
+
+#[test]
+fn test_run_reports_errors_to_the_error_sink() {
+    let mut streams = Streams {
+        input: std::io::empty(),
+        output: Vec::new(),
+        error: Vec::new(),
+    };
+
+    let result = run("print 1; print nope;", &mut streams, Options::default());
+
+    assert_eq!(result, Err(InterpreterError::Evaluation));
+    assert_eq!(String::from_utf8(streams.output).unwrap(), "1\n");
+    assert!(String::from_utf8(streams.error)
+        .unwrap()
+        .contains("Undefined variable nope"));
+}
+
+#[test]
+fn test_run_reads_from_the_input_source() {
+    let mut streams = Streams {
+        input: "gejang\n".as_bytes(),
+        output: Vec::new(),
+        error: std::io::sink(),
+    };
+
+    run("print len(read_line());", &mut streams, Options::default()).unwrap();
+
+    assert_eq!(String::from_utf8(streams.output).unwrap(), "6\n");
+}
+
+#[test]
+fn test_run_returns_the_last_expression_value() {
+    let mut streams = Streams {
+        input: std::io::empty(),
+        output: Vec::new(),
+        error: Vec::new(),
+    };
+
+    let value = run("var x = 20; x * 2 + 2;", &mut streams, Options::default());
+
+    assert_eq!(value, Ok(Some("42".to_string())));
+    assert!(streams.output.is_empty());
+}
+
+#[test]
+fn test_run_returns_when_the_script_exits() {
+    let mut streams = Streams {
+        input: std::io::empty(),
+        output: Vec::new(),
+        error: Vec::new(),
+    };
+
+    let result = run(
+        "print 1; exit(2); print 3;",
+        &mut streams,
+        Options::default(),
+    );
+
+    assert_eq!(result, Err(InterpreterError::Exit { code: 2 }));
+    assert_eq!(String::from_utf8(streams.output).unwrap(), "1\n");
+}